@@ -43,6 +43,13 @@ impl<'a> Selector<'a> {
         })
     }
 
+    pub fn terms(&self) -> Vec<&str> {
+        self.source_to_term_map
+            .values()
+            .map(|t| t.as_str())
+            .collect()
+    }
+
     pub fn sources(&self) -> Vec<&TextSource> {
         self.source_to_term_map.keys().copied().collect()
     }
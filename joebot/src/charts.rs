@@ -0,0 +1,405 @@
+use crate::JoeResult;
+use lazy_static::lazy_static;
+use plotters::{
+    prelude::*,
+    style::{register_font, FontStyle},
+};
+use serenity::{builder::CreateEmbed, http::AttachmentType, model::prelude::*, prelude::*};
+use std::borrow::Cow;
+
+const FONT_PATH: &str = "joker/font.ttf";
+const FONT_FAMILY: &str = "joebot";
+
+const WIDTH: u32 = 800;
+const HEIGHT: u32 = 450;
+const FILENAME: &str = "chart.png";
+
+const SPARKLINE_BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+const CLOUD_MIN_FONT_SIZE: f64 = 14.0;
+const CLOUD_MAX_FONT_SIZE: f64 = 72.0;
+const CLOUD_MARGIN: i32 = 16;
+
+const BACKGROUND: RGBColor = RGBColor(0x2f, 0x31, 0x36);
+const FOREGROUND: RGBColor = RGBColor(0xdc, 0xdd, 0xde);
+const GRID: RGBColor = RGBColor(0x40, 0x44, 0x4b);
+const PALETTE: [RGBColor; 6] = [
+    RGBColor(0xd0, 0x7a, 0x80),
+    RGBColor(0xe8, 0xc1, 0x6a),
+    RGBColor(0x7a, 0xb8, 0xd0),
+    RGBColor(0x9c, 0xd0, 0x7a),
+    RGBColor(0xb0, 0x8c, 0xd8),
+    RGBColor(0xe0, 0x9a, 0x5c),
+];
+
+lazy_static! {
+    static ref FONT: Result<(), String> = std::fs::read(FONT_PATH)
+        .map_err(|e| format!("Cannot read {}: {}", FONT_PATH, e))
+        .and_then(|bytes| {
+            // Fonts are registered for the lifetime of the process
+            let bytes = Box::leak(bytes.into_boxed_slice());
+            register_font(FONT_FAMILY, FontStyle::Normal, bytes)
+                .map_err(|_| format!("{} is not a valid font", FONT_PATH))
+        });
+}
+
+pub type Month = (i32, u32); // (year, month)
+
+pub fn months_between(first: Month, last: Month) -> Vec<Month> {
+    let (mut year, mut month) = first;
+    let mut months = Vec::new();
+    while (year, month) <= last {
+        months.push((year, month));
+        if month == 12 {
+            year += 1;
+            month = 1;
+        } else {
+            month += 1;
+        }
+    }
+    months
+}
+
+// Series counts are aligned with months
+pub fn activity_over_time(
+    title: &str,
+    months: &[Month],
+    series: &[(&str, Vec<usize>)],
+) -> JoeResult<Vec<u8>> {
+    let max_count = series
+        .iter()
+        .flat_map(|(_, counts)| counts.iter().copied())
+        .max()
+        .unwrap_or(0);
+
+    render(|root| {
+        let mut chart = ChartBuilder::on(root)
+            .caption(title, caption_style())
+            .margin(16)
+            .x_label_area_size(28)
+            .y_label_area_size(48)
+            .build_cartesian_2d(0..months.len().saturating_sub(1).max(1), 0..max_count + 1)?;
+        let month_label = |idx: &usize| months.get(*idx).map(format_month).unwrap_or_default();
+        chart
+            .configure_mesh()
+            .disable_x_mesh()
+            .x_labels(months.len().min(12))
+            .x_label_formatter(&month_label)
+            .label_style(label_style())
+            .axis_style(FOREGROUND)
+            .light_line_style(BACKGROUND)
+            .bold_line_style(GRID)
+            .draw()?;
+
+        for (i, (name, counts)) in series.iter().enumerate() {
+            let color = PALETTE[i % PALETTE.len()];
+            chart
+                .draw_series(LineSeries::new(
+                    counts.iter().copied().enumerate(),
+                    color.stroke_width(2),
+                ))?
+                .label(*name)
+                .legend(move |(x, y)| {
+                    PathElement::new(vec![(x, y), (x + 16, y)], color.stroke_width(2))
+                });
+        }
+        if series.len() > 1 {
+            chart
+                .configure_series_labels()
+                .position(SeriesLabelPosition::UpperLeft)
+                .background_style(BACKGROUND.mix(0.8))
+                .border_style(GRID)
+                .label_font(label_style())
+                .draw()?;
+        }
+        Ok(())
+    })
+}
+
+pub fn timeline(title: &str, per_month: &[(Month, usize)]) -> JoeResult<Vec<u8>> {
+    let max_count = per_month.iter().map(|&(_, c)| c).max().unwrap_or(0);
+
+    render(|root| {
+        let mut chart = ChartBuilder::on(root)
+            .caption(title, caption_style())
+            .margin(16)
+            .x_label_area_size(28)
+            .y_label_area_size(48)
+            .build_cartesian_2d(
+                // Integer ranges include their end, so there is one segment per value
+                (0..per_month.len().saturating_sub(1)).into_segmented(),
+                0..max_count + 1,
+            )?;
+        let month_label = |v: &SegmentValue<usize>| match v {
+            SegmentValue::CenterOf(idx) => per_month
+                .get(*idx)
+                .map(|(m, _)| format_month(m))
+                .unwrap_or_default(),
+            _ => String::new(),
+        };
+        chart
+            .configure_mesh()
+            .disable_x_mesh()
+            .x_labels(per_month.len().min(12))
+            .x_label_formatter(&month_label)
+            .label_style(label_style())
+            .axis_style(FOREGROUND)
+            .light_line_style(BACKGROUND)
+            .bold_line_style(GRID)
+            .draw()?;
+
+        chart.draw_series(per_month.iter().enumerate().map(|(idx, &(_, count))| {
+            let mut bar = Rectangle::new(
+                [
+                    (SegmentValue::Exact(idx), 0),
+                    (SegmentValue::Exact(idx + 1), count),
+                ],
+                PALETTE[0].filled(),
+            );
+            bar.set_margin(0, 0, 1, 1);
+            bar
+        }))?;
+        Ok(())
+    })
+}
+
+// Each cell is shaded relative to the busiest hour of its row
+pub fn hour_heatmap(title: &str, rows: &[(&str, [usize; 24])]) -> JoeResult<Vec<u8>> {
+    render(|root| {
+        let mut chart = ChartBuilder::on(root)
+            .caption(title, caption_style())
+            .margin(16)
+            .x_label_area_size(28)
+            .y_label_area_size(96)
+            .build_cartesian_2d(
+                (0..23usize).into_segmented(),
+                (0..rows.len().saturating_sub(1)).into_segmented(),
+            )?;
+        let hour_label = |v: &SegmentValue<usize>| match v {
+            SegmentValue::CenterOf(hour) => hour.to_string(),
+            _ => String::new(),
+        };
+        let row_label = |v: &SegmentValue<usize>| match v {
+            SegmentValue::CenterOf(idx) if *idx < rows.len() => {
+                rows[rows.len() - 1 - idx].0.to_owned()
+            }
+            _ => String::new(),
+        };
+        chart
+            .configure_mesh()
+            .disable_mesh()
+            .x_labels(24)
+            .y_labels(rows.len())
+            .x_label_formatter(&hour_label)
+            .y_label_formatter(&row_label)
+            .label_style(label_style())
+            .axis_style(FOREGROUND)
+            .draw()?;
+
+        for (row_idx, (_, hours)) in rows.iter().enumerate() {
+            let y = rows.len() - 1 - row_idx;
+            let max_count = hours.iter().copied().max().unwrap_or(0).max(1);
+            chart.draw_series(hours.iter().enumerate().map(|(hour, &count)| {
+                let intensity = count as f64 / max_count as f64;
+                let mut cell = Rectangle::new(
+                    [
+                        (SegmentValue::Exact(hour), SegmentValue::Exact(y)),
+                        (SegmentValue::Exact(hour + 1), SegmentValue::Exact(y + 1)),
+                    ],
+                    PALETTE[0].mix(0.1 + 0.9 * intensity).filled(),
+                );
+                cell.set_margin(1, 1, 1, 1);
+                cell
+            }))?;
+        }
+        Ok(())
+    })
+}
+
+// The most frequent words go in the middle, the ones that don't fit are left out
+pub fn word_cloud(words: &[(String, usize)]) -> JoeResult<Vec<u8>> {
+    let max_count = words.iter().map(|&(_, c)| c).max().unwrap_or(0).max(1) as f64;
+    let font_size = |count: usize| {
+        let weight = (count as f64 / max_count).sqrt();
+        CLOUD_MIN_FONT_SIZE + (CLOUD_MAX_FONT_SIZE - CLOUD_MIN_FONT_SIZE) * weight
+    };
+
+    render(|root| {
+        let styles = words
+            .iter()
+            .enumerate()
+            .map(|(i, &(_, count))| {
+                (FONT_FAMILY, font_size(count))
+                    .into_font()
+                    .color(&PALETTE[i % PALETTE.len()])
+            })
+            .collect::<Vec<_>>();
+        let sizes = words
+            .iter()
+            .zip(&styles)
+            .map(|((word, _), style)| root.estimate_text_size(word, style))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let positions = layout_cloud(&sizes, (WIDTH as i32, HEIGHT as i32));
+        for (((word, _), style), pos) in words.iter().zip(&styles).zip(positions) {
+            if let Some(pos) = pos {
+                root.draw_text(word, style, pos)?;
+            }
+        }
+        Ok(())
+    })
+}
+
+// Walks outwards along a spiral until each box fits without overlapping the ones before it
+fn layout_cloud(sizes: &[(u32, u32)], (width, height): (i32, i32)) -> Vec<Option<(i32, i32)>> {
+    let mut placed: Vec<(i32, i32, i32, i32)> = Vec::new();
+    let fits = |(x0, y0, x1, y1): (i32, i32, i32, i32), placed: &[(i32, i32, i32, i32)]| {
+        x0 >= CLOUD_MARGIN
+            && y0 >= CLOUD_MARGIN
+            && x1 <= width - CLOUD_MARGIN
+            && y1 <= height - CLOUD_MARGIN
+            && placed
+                .iter()
+                .all(|&(px0, py0, px1, py1)| x1 <= px0 || x0 >= px1 || y1 <= py0 || y0 >= py1)
+    };
+
+    sizes
+        .iter()
+        .map(|&(w, h)| {
+            let (w, h) = (w as i32, h as i32);
+            let max_radius = (width.max(height) / 2) as f64;
+            let mut angle = 0.0f64;
+            loop {
+                // The spiral is stretched horizontally to match the shape of the image
+                let radius = 2.0 * angle;
+                if radius > max_radius {
+                    return None;
+                }
+                let cx = width / 2 + (radius * angle.cos() * width as f64 / height as f64) as i32;
+                let cy = height / 2 + (radius * angle.sin()) as i32;
+                let rect = (cx - w / 2, cy - h / 2, cx - w / 2 + w, cy - h / 2 + h);
+                if fits(rect, &placed) {
+                    placed.push(rect);
+                    return Some((rect.0, rect.1));
+                }
+                angle += 0.1;
+            }
+        })
+        .collect()
+}
+
+// Levels go from 0 to 1, None leaves a gap
+pub fn sparkline(levels: impl IntoIterator<Item = Option<f64>>) -> String {
+    levels
+        .into_iter()
+        .map(|level| match level {
+            Some(level) => {
+                let bar = (level * SPARKLINE_BARS.len() as f64).ceil() as usize;
+                SPARKLINE_BARS[bar.clamp(1, SPARKLINE_BARS.len()) - 1]
+            }
+            None => ' ',
+        })
+        .collect()
+}
+
+// Posts the chart as an embed image, the way Joker posts its pictures
+pub fn send_chart<F>(ctx: &Context, channel_id: ChannelId, png: Vec<u8>, f: F) -> JoeResult<Message>
+where
+    F: FnOnce(&mut CreateEmbed) -> &mut CreateEmbed,
+{
+    let posted = channel_id.send_files(
+        &ctx.http,
+        vec![AttachmentType::Bytes {
+            data: Cow::from(png),
+            filename: FILENAME.into(),
+        }],
+        |m| {
+            m.embed(|e| {
+                e.color(crate::EMBED_COLOR);
+                e.attachment(FILENAME);
+                f(e)
+            });
+            m
+        },
+    )?;
+    Ok(posted)
+}
+
+fn render<F>(draw: F) -> JoeResult<Vec<u8>>
+where
+    F: FnOnce(&DrawingArea<BitMapBackend, plotters::coord::Shift>) -> JoeResult<()>,
+{
+    FONT.clone()?;
+
+    let mut buffer = vec![0u8; (WIDTH * HEIGHT * 3) as usize];
+    {
+        let root = BitMapBackend::with_buffer(&mut buffer, (WIDTH, HEIGHT)).into_drawing_area();
+        root.fill(&BACKGROUND)?;
+        draw(&root)?;
+        root.present()?;
+    }
+
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, WIDTH, HEIGHT);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(&buffer)?;
+    Ok(png)
+}
+
+fn caption_style() -> TextStyle<'static> {
+    (FONT_FAMILY, 28).into_font().color(&FOREGROUND)
+}
+
+fn label_style() -> TextStyle<'static> {
+    (FONT_FAMILY, 14).into_font().color(&FOREGROUND)
+}
+
+fn format_month(&(year, month): &Month) -> String {
+    format!("{:02}.{:02}", month, year % 100)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_months_between() {
+        assert_eq!(
+            months_between((2019, 11), (2020, 2)),
+            vec![(2019, 11), (2019, 12), (2020, 1), (2020, 2)]
+        );
+        assert_eq!(months_between((2020, 5), (2020, 5)), vec![(2020, 5)]);
+        assert!(months_between((2020, 5), (2020, 4)).is_empty());
+    }
+
+    #[test]
+    fn test_sparkline() {
+        assert_eq!(
+            sparkline(vec![Some(0.0), Some(0.1), None, Some(0.5), Some(1.0)]),
+            "▁▁ ▄█"
+        );
+        assert_eq!(sparkline(vec![]), "");
+    }
+
+    #[test]
+    fn test_layout_cloud() {
+        let sizes = [(300, 70), (120, 40), (120, 40), (80, 20), (1000, 20)];
+        let positions = layout_cloud(&sizes, (800, 450));
+        assert_eq!(positions[0], Some((250, 190)));
+        assert_eq!(positions[4], None);
+
+        let rects = sizes
+            .iter()
+            .zip(&positions)
+            .filter_map(|(&(w, h), pos)| pos.map(|(x, y)| (x, y, x + w as i32, y + h as i32)))
+            .collect::<Vec<_>>();
+        assert_eq!(rects.len(), 4);
+        for (i, a) in rects.iter().enumerate() {
+            assert!(a.0 >= CLOUD_MARGIN && a.2 <= 800 - CLOUD_MARGIN);
+            for b in &rects[i + 1..] {
+                assert!(a.2 <= b.0 || a.0 >= b.2 || a.3 <= b.1 || a.1 >= b.3);
+            }
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use crate::{
+    charts::{self, Month},
+    messages::MessageDump,
+    utils::split_command_rest,
+    JoeResult,
+};
+use chrono::{Datelike, Timelike};
+use serenity::{model::prelude::*, prelude::*};
+use std::collections::HashMap;
+
+const TIMELINE_AUTHORS: usize = 6;
+const HEATMAP_AUTHORS: usize = 10;
+
+pub struct Activity<'a> {
+    messages: &'a RwLock<MessageDump>,
+}
+
+impl<'a> Activity<'a> {
+    pub fn new(messages: &'a RwLock<MessageDump>) -> Self {
+        Self { messages }
+    }
+}
+
+impl<'a> super::Command for Activity<'a> {
+    fn handle_message(&mut self, ctx: &Context, msg: &Message) -> JoeResult<bool> {
+        let (command, args) = split_command_rest(msg);
+        if command != "!активность" {
+            return Ok(false);
+        }
+
+        let messages = self.messages.read();
+        let (chart, title) = match args {
+            "часы" => {
+                let rows = hours_by_author(&messages, HEATMAP_AUTHORS);
+                let rows = rows
+                    .iter()
+                    .map(|&(author_idx, hours)| {
+                        (messages.authors[author_idx].short_name.as_str(), hours)
+                    })
+                    .collect::<Vec<_>>();
+                let chart = charts::hour_heatmap("сообщения по часам", &rows)?;
+                (chart, "Когда в салуне шумно")
+            }
+            _ => {
+                let (months, series) = months_by_author(&messages, TIMELINE_AUTHORS);
+                let series = series
+                    .into_iter()
+                    .map(|(author_idx, counts)| {
+                        (messages.authors[author_idx].short_name.as_str(), counts)
+                    })
+                    .collect::<Vec<_>>();
+                let chart = charts::activity_over_time("сообщения в месяц", &months, &series)?;
+                (chart, "Кто и когда держал салун на ушах")
+            }
+        };
+        charts::send_chart(ctx, msg.channel_id, chart, |e| {
+            e.title(title);
+            e.footer(|f| f.text("!активность часы — по времени суток"));
+            e
+        })?;
+
+        Ok(true)
+    }
+}
+
+fn months_by_author(
+    messages: &MessageDump,
+    num_authors: usize,
+) -> (Vec<Month>, Vec<(usize, Vec<usize>)>) {
+    let mut counts: HashMap<usize, HashMap<Month, usize>> = HashMap::new();
+    for m in messages.visible_texts().filter(|m| !m.continuation) {
+        *counts
+            .entry(m.author_idx)
+            .or_default()
+            .entry((m.date.year(), m.date.month()))
+            .or_insert(0) += 1;
+    }
+
+    let all_months = counts.values().flat_map(|c| c.keys().copied());
+    let months = match (all_months.clone().min(), all_months.max()) {
+        (Some(first), Some(last)) => charts::months_between(first, last),
+        _ => vec![],
+    };
+
+    let mut series = counts
+        .into_iter()
+        .map(|(author_idx, per_month)| {
+            let counts = months
+                .iter()
+                .map(|m| per_month.get(m).copied().unwrap_or(0))
+                .collect::<Vec<_>>();
+            (author_idx, counts)
+        })
+        .collect::<Vec<_>>();
+    series.sort_by_key(|(author_idx, counts)| {
+        (std::cmp::Reverse(counts.iter().sum::<usize>()), *author_idx)
+    });
+    series.truncate(num_authors);
+
+    (months, series)
+}
+
+fn hours_by_author(messages: &MessageDump, num_authors: usize) -> Vec<(usize, [usize; 24])> {
+    let mut counts: HashMap<usize, [usize; 24]> = HashMap::new();
+    for m in messages.visible_texts().filter(|m| !m.continuation) {
+        counts.entry(m.author_idx).or_insert([0; 24])[m.date.hour() as usize] += 1;
+    }
+
+    let mut rows = counts.into_iter().collect::<Vec<_>>();
+    rows.sort_by_key(|(author_idx, hours)| {
+        (std::cmp::Reverse(hours.iter().sum::<usize>()), *author_idx)
+    });
+    rows.truncate(num_authors);
+    rows
+}
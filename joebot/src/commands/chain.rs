@@ -1,4 +1,4 @@
-use crate::{utils::split_command_rest, JoeResult};
+use crate::{config::UserMatcher, messages::MessageDump, utils::split_command_rest, JoeResult};
 use circular_queue::CircularQueue;
 use joebot_markov_chain::{ChainGenerate, Datestamp, MarkovChain, Selector, SelectorError};
 use phf::phf_map;
@@ -18,16 +18,24 @@ static DATE_RANGE_MAP: phf::Map<&'static str, (Datestamp, Datestamp)> = phf_map!
     "шестой сем" => (Datestamp { year: 2020, day: 28 }, Datestamp { year: 2020, day: 183 }),
 };
 
-pub struct Chain {
-    chain: MarkovChain,
+pub struct Chain<'a> {
+    chain: &'a MarkovChain,
+    messages: &'a RwLock<MessageDump>,
+    user_matcher: &'a UserMatcher,
     rng: SmallRng,
     prompt_history: CircularQueue<(MessageId, String)>,
 }
 
-impl Chain {
-    pub fn new(chain: MarkovChain) -> Self {
+impl<'a> Chain<'a> {
+    pub fn new(
+        chain: &'a MarkovChain,
+        messages: &'a RwLock<MessageDump>,
+        user_matcher: &'a UserMatcher,
+    ) -> Self {
         Self {
             chain,
+            messages,
+            user_matcher,
             rng: SmallRng::from_entropy(),
             prompt_history: CircularQueue::with_capacity(100),
         }
@@ -129,7 +137,7 @@ fn chain_sources<'a, 'b>(
     m
 }
 
-impl super::Command for Chain {
+impl<'a> super::Command for Chain<'a> {
     fn handle_message(&mut self, ctx: &Context, msg: &Message) -> JoeResult<bool> {
         let (command, args_raw) = split_command_rest(msg);
         let args = args_raw.to_lowercase();
@@ -140,7 +148,7 @@ impl super::Command for Chain {
             }
             "!mashupstars" => {
                 msg.channel_id
-                    .send_message(&ctx.http, |m| chain_sources(&self.chain, m))?;
+                    .send_message(&ctx.http, |m| chain_sources(self.chain, m))?;
                 Ok(true)
             }
             _ => Ok(false),
@@ -167,7 +175,7 @@ impl super::Command for Chain {
     }
 }
 
-impl Chain {
+impl<'a> Chain<'a> {
     fn handle_mashup(
         &mut self,
         ctx: &Context,
@@ -193,7 +201,13 @@ impl Chain {
         } else {
             (args.as_str(), None)
         };
-        match Selector::new(&self.chain, names_str, date_range) {
+        match Selector::new(self.chain, names_str, date_range) {
+            Ok(ref selector) if self.selects_opted_out(selector) => {
+                channel_id.say(
+                    &ctx.http,
+                    "Этот ковбой предпочитает держать язык за зубами.",
+                )?;
+            }
             Ok(selector) => {
                 let text = self
                     .chain
@@ -212,4 +226,14 @@ impl Chain {
         };
         Ok(())
     }
+
+    fn selects_opted_out(&self, selector: &Selector) -> bool {
+        let messages = self.messages.read();
+        selector.terms().iter().any(|term| {
+            messages
+                .opted_out()
+                .iter()
+                .any(|name| self.user_matcher.matches_short_name(term, name))
+        })
+    }
 }
@@ -0,0 +1,353 @@
+use crate::{config::UserMatcher, messages::MessageDump, utils::split_command_rest, JoeResult};
+use chrono::NaiveDate;
+use circular_queue::CircularQueue;
+use regex::{Regex, RegexBuilder};
+use serenity::{builder::CreateEmbed, model::prelude::*, prelude::*};
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+const MAX_PATTERN_LEN: usize = 200;
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+const SEARCH_TIMEOUT: Duration = Duration::from_secs(2);
+const MAX_RESULTS: usize = 500;
+
+const PAGE_SIZE: usize = 10;
+const SNIPPET_LEN: usize = 150;
+const SNIPPET_LEAD: usize = 40;
+const PREV_PAGE_REACTION: char = '👈';
+const NEXT_PAGE_REACTION: char = '👉';
+
+pub struct Grep<'a> {
+    messages: &'a RwLock<MessageDump>,
+    user_matcher: &'a UserMatcher,
+    searches: CircularQueue<(MessageId, Search)>,
+}
+
+struct Search {
+    regex: Regex,
+    matches: Vec<usize>,
+    incomplete: bool,
+    page: usize,
+}
+
+#[derive(Default)]
+struct Query<'q> {
+    author: Option<&'q str>,
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+    pattern: &'q str,
+}
+
+impl<'a> Grep<'a> {
+    pub fn new(messages: &'a RwLock<MessageDump>, user_matcher: &'a UserMatcher) -> Self {
+        Self {
+            messages,
+            user_matcher,
+            searches: CircularQueue::with_capacity(20),
+        }
+    }
+}
+
+fn grep_help(e: &mut CreateEmbed) -> &mut CreateEmbed {
+    e.color(crate::EMBED_COLOR);
+    e.title("grep");
+    e.description(
+        r#"
+Поищем в архивах салуна по регулярному выражению:
+`!grep бонч|итмо`
+
+Только сообщения одного из жителей:
+`!grep кто:денко итмо`
+
+Только за определенное время:
+`!grep с:01.09.2019 до:31.12.2019 сессия`"#,
+    );
+    e
+}
+
+impl<'a> super::Command for Grep<'a> {
+    fn handle_message(&mut self, ctx: &Context, msg: &Message) -> JoeResult<bool> {
+        let (command, args) = split_command_rest(msg);
+        if command != "!grep" {
+            return Ok(false);
+        }
+
+        let query = match parse_query(args) {
+            Ok(query) if !query.pattern.is_empty() => query,
+            Ok(_) => {
+                msg.channel_id
+                    .send_message(&ctx.http, |m| m.embed(grep_help))?;
+                return Ok(true);
+            }
+            Err(e) => {
+                msg.channel_id.say(&ctx.http, e)?;
+                return Ok(true);
+            }
+        };
+        if query.pattern.chars().count() > MAX_PATTERN_LEN {
+            msg.channel_id.say(
+                &ctx.http,
+                "Полегче, ковбой, такой длинный запрос мне не осилить.",
+            )?;
+            return Ok(true);
+        }
+        let regex = match RegexBuilder::new(query.pattern)
+            .case_insensitive(true)
+            .size_limit(REGEX_SIZE_LIMIT)
+            .dfa_size_limit(REGEX_SIZE_LIMIT)
+            .build()
+        {
+            Ok(regex) => regex,
+            Err(e) => {
+                msg.channel_id
+                    .say(&ctx.http, format!("Не понял запрос, приятель: {}", e))?;
+                return Ok(true);
+            }
+        };
+
+        let messages = self.messages.read();
+        let author_idx = match query.author {
+            Some(name) => match self.user_matcher.find_author(&messages.authors, name) {
+                Some(idx) => Some(idx),
+                None => {
+                    msg.channel_id
+                        .say(&ctx.http, format!("{}? Не слыхал о таком.", name))?;
+                    return Ok(true);
+                }
+            },
+            None => None,
+        };
+
+        let search = search(&messages, regex, author_idx, query.since, query.until);
+        if search.matches.is_empty() {
+            let reply = if search.incomplete {
+                "Я так долго искал, что забыл, что именно. Попробуй запрос попроще."
+            } else {
+                r"¯\_(ツ)_/¯"
+            };
+            msg.channel_id.say(&ctx.http, reply)?;
+            return Ok(true);
+        }
+
+        let posted = msg.channel_id.send_message(&ctx.http, |m| {
+            m.embed(|e| search_page(e, &messages, &search));
+            if search.num_pages() > 1 {
+                m.reactions(vec![PREV_PAGE_REACTION, NEXT_PAGE_REACTION]);
+            }
+            m
+        })?;
+        self.searches.push((posted.id, search));
+
+        Ok(true)
+    }
+
+    fn handle_reaction(&mut self, ctx: &Context, rct: &Reaction) -> JoeResult<bool> {
+        let step: isize = match &rct.emoji {
+            ReactionType::Unicode(e) if *e == PREV_PAGE_REACTION.to_string() => -1,
+            ReactionType::Unicode(e) if *e == NEXT_PAGE_REACTION.to_string() => 1,
+            _ => return Ok(false),
+        };
+        let search = match self
+            .searches
+            .iter_mut()
+            .find(|(mid, _)| *mid == rct.message_id)
+        {
+            Some((_, search)) => search,
+            None => return Ok(false),
+        };
+
+        let page = search.page as isize + step;
+        if page < 0 || page as usize >= search.num_pages() {
+            return Ok(true);
+        }
+        search.page = page as usize;
+
+        let messages = self.messages.read();
+        rct.channel_id
+            .edit_message(&ctx.http, rct.message_id, |m| {
+                m.embed(|e| search_page(e, &messages, search))
+            })?;
+        // Lets the user flip the page again with the same reaction.
+        // Requires the Manage Messages permission, so failures are not fatal
+        rct.delete(ctx).ok();
+
+        Ok(true)
+    }
+}
+
+impl Search {
+    fn num_pages(&self) -> usize {
+        self.matches.len().div_ceil(PAGE_SIZE)
+    }
+}
+
+fn parse_query(args: &str) -> Result<Query<'_>, String> {
+    let mut query = Query::default();
+    let mut rest = args.trim_start();
+    loop {
+        let (token, after) = match rest.find(char::is_whitespace) {
+            Some(end) => (&rest[..end], rest[end..].trim_start()),
+            None => (rest, ""),
+        };
+        match token.splitn(2, ':').collect::<Vec<_>>()[..] {
+            ["кто", name] if !name.is_empty() => query.author = Some(name),
+            ["с", date] => query.since = Some(parse_date(date)?),
+            ["до", date] => query.until = Some(parse_date(date)?),
+            _ => {
+                query.pattern = rest;
+                return Ok(query);
+            }
+        }
+        rest = after;
+    }
+}
+
+fn parse_date(date: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(date, "%d.%m.%Y")
+        .map_err(|_| format!("{}? Даты я пишу как 31.12.2019.", date))
+}
+
+fn search(
+    messages: &MessageDump,
+    regex: Regex,
+    author_idx: Option<usize>,
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+) -> Search {
+    let started = Instant::now();
+    let mut matches = Vec::new();
+    let mut incomplete = false;
+
+    // Newest messages first
+    for (idx, m) in messages.texts.iter().enumerate().rev() {
+        if idx % 1000 == 0 && started.elapsed() > SEARCH_TIMEOUT {
+            incomplete = true;
+            break;
+        }
+        let date = m.date.date();
+        if matches!(author_idx, Some(a) if a != m.author_idx)
+            || matches!(since, Some(d) if date < d)
+            || matches!(until, Some(d) if date > d)
+            || !messages.is_visible(m)
+        {
+            continue;
+        }
+        if regex.is_match(&m.text) {
+            matches.push(idx);
+            if matches.len() == MAX_RESULTS {
+                incomplete = true;
+                break;
+            }
+        }
+    }
+
+    Search {
+        regex,
+        matches,
+        incomplete,
+        page: 0,
+    }
+}
+
+fn search_page<'e>(
+    e: &'e mut CreateEmbed,
+    messages: &MessageDump,
+    search: &Search,
+) -> &'e mut CreateEmbed {
+    let mut description = String::new();
+    let page_matches = search
+        .matches
+        .iter()
+        .skip(search.page * PAGE_SIZE)
+        .take(PAGE_SIZE);
+    for &idx in page_matches {
+        let m = &messages.texts[idx];
+        writeln!(
+            &mut description,
+            "`{}` **{}**: {}",
+            m.date.format("%d.%m.%Y"),
+            messages.authors[m.author_idx].short_name,
+            snippet(&m.text, &search.regex)
+        )
+        .unwrap();
+    }
+
+    let num_matches = if search.incomplete {
+        format!("{}+", search.matches.len())
+    } else {
+        search.matches.len().to_string()
+    };
+    e.color(crate::EMBED_COLOR);
+    e.title(format!("/{}/ — {} совпадений", search.regex, num_matches));
+    e.description(description);
+    e.footer(|f| {
+        f.text(format!("{} / {}", search.page + 1, search.num_pages()));
+        f
+    });
+    e
+}
+
+fn snippet(text: &str, regex: &Regex) -> String {
+    let text = text.replace('\n', " ");
+    let (start, end) = regex
+        .find(&text)
+        .map(|m| (m.start(), m.end()))
+        .unwrap_or((0, 0));
+
+    let lead_start = text[..start]
+        .char_indices()
+        .rev()
+        .nth(SNIPPET_LEAD - 1)
+        .map(|(i, _)| i)
+        .unwrap_or(0);
+    let lead = &text[lead_start..start];
+    let matched = text[start..end]
+        .chars()
+        .take(SNIPPET_LEN)
+        .collect::<String>();
+    let tail_len = SNIPPET_LEN.saturating_sub(lead.chars().count() + matched.chars().count());
+    let tail = text[end..].chars().take(tail_len).collect::<String>();
+
+    let mut snippet = String::new();
+    if lead_start > 0 {
+        snippet.push('…');
+    }
+    snippet.push_str(lead);
+    if !matched.is_empty() {
+        write!(&mut snippet, "__{}__", matched).unwrap();
+    }
+    snippet.push_str(&tail);
+    if end + tail.len() < text.len() {
+        snippet.push('…');
+    }
+    snippet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_query() {
+        let query = parse_query("кто:денко с:01.09.2019 итмо|бонч").unwrap();
+        assert_eq!(query.author, Some("денко"));
+        assert_eq!(query.since, Some(NaiveDate::from_ymd(2019, 9, 1)));
+        assert_eq!(query.until, None);
+        assert_eq!(query.pattern, "итмо|бонч");
+
+        assert_eq!(parse_query("a b:c").unwrap().pattern, "a b:c");
+        assert!(parse_query("до:вчера итмо").is_err());
+    }
+
+    #[test]
+    fn test_snippet() {
+        let regex = Regex::new("итмо").unwrap();
+        assert_eq!(snippet("мы в итмо", &regex), "мы в __итмо__");
+
+        let long = format!("{} итмо {}", "а".repeat(100), "б".repeat(200));
+        let s = snippet(&long, &regex);
+        assert!(s.starts_with('…') && s.ends_with('…'));
+        assert!(s.contains("__итмо__"));
+        assert_eq!(s.chars().count(), SNIPPET_LEN + "…____…".chars().count());
+    }
+}
@@ -1,26 +1,27 @@
-use crate::{
-    messages::{Author, MessageDump},
-    JoeResult,
-};
+use super::quote::{QuoteHistory, CONTEXT_REACTION};
+use crate::{messages::MessageDump, JoeResult};
 use rand::{rngs::SmallRng, SeedableRng, seq::SliceRandom};
 use serenity::{model::prelude::*, prelude::*};
 use std::io::{Read, Write};
 use std::os::unix::net::UnixStream;
 
 pub struct Img2msg<'a> {
-    messages: &'a MessageDump,
+    messages: &'a RwLock<MessageDump>,
     rng: SmallRng,
-    classifier: UnixStream,
+    classifier: &'a Mutex<UnixStream>,
+    quote_history: QuoteHistory,
 }
 
 impl<'a> Img2msg<'a> {
-    pub fn new(messages: &'a MessageDump) -> JoeResult<Self> {
-        let classifier = UnixStream::connect("imclassif.sock")?;
-
+    pub fn new(
+        messages: &'a RwLock<MessageDump>,
+        classifier: &'a Mutex<UnixStream>,
+    ) -> JoeResult<Self> {
         Ok(Self {
             messages,
             rng: SmallRng::from_entropy(),
             classifier,
+            quote_history: QuoteHistory::new(),
         })
     }
 }
@@ -31,16 +32,17 @@ impl<'a> super::Command for Img2msg<'a> {
             Some(a) if a.width.is_some() => {
                 let data = a.download()?;
 
-                self.classifier
-                    .write_all(&(data.len() as u32).to_be_bytes())?;
-                self.classifier.write_all(&data)?;
+                let mut classifier = self.classifier.lock();
+                classifier.write_all(&(data.len() as u32).to_be_bytes())?;
+                classifier.write_all(&data)?;
 
                 let mut result_size_bytes = [0u8; 4];
-                self.classifier.read_exact(&mut result_size_bytes)?;
+                classifier.read_exact(&mut result_size_bytes)?;
                 let result_size = u32::from_be_bytes(result_size_bytes);
 
                 let mut result_bytes = vec![0; result_size as usize];
-                self.classifier.read_exact(&mut result_bytes)?;
+                classifier.read_exact(&mut result_bytes)?;
+                drop(classifier);
 
                 let result = std::str::from_utf8(&result_bytes)?;
                 let tiered_kw_stems = result
@@ -48,27 +50,30 @@ impl<'a> super::Command for Img2msg<'a> {
                     .map(|ss| ss.split(',').collect::<Vec<_>>())
                     .collect::<Vec<_>>();
 
-                if let Some((author, text)) =
-                    pick_text(&self.messages, &mut self.rng, &tiered_kw_stems)
-                {
+                let messages = self.messages.read();
+                if let Some(pick) = pick_text(&messages, &mut self.rng, &tiered_kw_stems) {
+                    let author = &messages.authors[pick.author_idx];
                     let kw_stems = tiered_kw_stems
                         .iter()
                         .map(|ss| *ss.first().unwrap())
                         .collect::<Vec<_>>()
                         .join(", ");
 
-                    msg.channel_id.send_message(&ctx.http, |m| {
+                    let posted = msg.channel_id.send_message(&ctx.http, |m| {
                         m.embed(|e| {
                             e.color(crate::EMBED_COLOR);
-                            e.description(text);
+                            e.description(&pick.text);
                             e.footer(|f| {
                                 f.text(format!("— {} о {}", author.short_name, kw_stems));
                                 f
                             });
                             e
                         });
+                        m.reactions(vec![CONTEXT_REACTION]);
                         m
                     })?;
+                    self.quote_history
+                        .push(posted.id, messages.index_of(pick).into_iter().collect());
                 }
 
                 Ok(true)
@@ -76,16 +81,21 @@ impl<'a> super::Command for Img2msg<'a> {
             _ => Ok(false),
         }
     }
+
+    fn handle_reaction(&mut self, ctx: &Context, rct: &Reaction) -> JoeResult<bool> {
+        self.quote_history
+            .handle_reaction(ctx, rct, &self.messages.read())
+    }
 }
 
 fn pick_text<'a>(
     messages: &'a MessageDump,
     rng: &mut SmallRng,
     keyword_stems_by_tier: &[Vec<&str>],
-) -> Option<(&'a Author, &'a str)> {
+) -> Option<&'a crate::messages::Message> {
     for kws in keyword_stems_by_tier {
         if let Some(m) = messages.containing_any_words(&&kws[..]).choose(rng) {
-            return Some((&messages.authors[m.author_idx], &m.text));
+            return Some(m);
         }
     }
     None
@@ -1,7 +1,5 @@
-use crate::{
-    messages::{Author, MessageDump},
-    JoeResult,
-};
+use super::quote::{QuoteHistory, CONTEXT_REACTION};
+use crate::{messages::MessageDump, JoeResult};
 use rand::{rngs::SmallRng, seq::SliceRandom, SeedableRng};
 use regex::Regex;
 use serenity::{http::AttachmentType, model::prelude::*, prelude::*};
@@ -12,20 +10,23 @@ use std::os::unix::net::UnixStream;
 mod template;
 
 pub struct Joker<'a> {
-    messages: &'a MessageDump,
+    messages: &'a RwLock<MessageDump>,
     trigger_regex: Regex,
     rng: SmallRng,
     templates: Vec<template::Template>,
-    random_text_generator: UnixStream,
+    random_text_generator: &'a Mutex<UnixStream>,
+    quote_history: QuoteHistory,
 }
 
 impl<'a> Joker<'a> {
-    pub fn new(messages: &'a MessageDump) -> JoeResult<Self> {
+    pub fn new(
+        messages: &'a RwLock<MessageDump>,
+        random_text_generator: &'a Mutex<UnixStream>,
+    ) -> JoeResult<Self> {
         let trigger_regex =
             Regex::new(r"(?i)(?:джокер)\s*(?P<len>[+]+)?(?:\s*про\s+(?:(?P<prompt_top>.+)\s+и\s+(?P<prompt_bottom>.+)|(?P<prompt>.+)))?").unwrap();
         let rng = SmallRng::from_entropy();
         let templates = template::load_jpg_templates("joker")?;
-        let random_text_generator = UnixStream::connect("randtext.sock")?;
 
         Ok(Self {
             messages,
@@ -33,6 +34,7 @@ impl<'a> Joker<'a> {
             rng,
             templates,
             random_text_generator,
+            quote_history: QuoteHistory::new(),
         })
     }
 }
@@ -48,7 +50,7 @@ impl<'a> super::Command for Joker<'a> {
 
             if top_prompt.is_none() && bottom_prompt.is_none() {
                 let (top_text, bottom_text) =
-                    generate_top_bottom_text(&mut self.random_text_generator)?;
+                    generate_top_bottom_text(&mut self.random_text_generator.lock())?;
 
                 self.send_image(
                     msg.channel_id,
@@ -56,6 +58,7 @@ impl<'a> super::Command for Joker<'a> {
                     &top_text,
                     &bottom_text,
                     "This meme was made by quest snickers and joe",
+                    vec![],
                 )?;
             } else {
                 let min_words = captures
@@ -63,22 +66,31 @@ impl<'a> super::Command for Joker<'a> {
                     .map(|pluses| pluses.as_str().len())
                     .unwrap_or(0);
 
-                let picks = pick_text(&self.messages, &mut self.rng, min_words, top_prompt)
-                    .and_then(|top_pick| {
-                        pick_text(&self.messages, &mut self.rng, min_words, bottom_prompt)
+                let messages = self.messages.read();
+                let picks = pick_text(&messages, &mut self.rng, min_words, top_prompt).and_then(
+                    |top_pick| {
+                        pick_text(&messages, &mut self.rng, min_words, bottom_prompt)
                             .map(|bottom_pick| (top_pick, bottom_pick))
-                    });
-
-                if let Some(((top_author, top_text), (bottom_author, bottom_text))) = picks {
+                    },
+                );
+
+                if let Some((top_pick, bottom_pick)) = picks {
+                    let top_author = &messages.authors[top_pick.author_idx];
+                    let bottom_author = &messages.authors[bottom_pick.author_idx];
+                    let quoted_idxs = [top_pick, bottom_pick]
+                        .iter()
+                        .filter_map(|&m| messages.index_of(m))
+                        .collect();
                     self.send_image(
                         msg.channel_id,
                         ctx,
-                        top_text,
-                        bottom_text,
+                        &top_pick.text,
+                        &bottom_pick.text,
                         &format!(
                             "This meme was made by {}, {} and joe",
                             top_author.short_name, bottom_author.short_name
                         ),
+                        quoted_idxs,
                     )?;
                 } else {
                     msg.channel_id
@@ -91,6 +103,11 @@ impl<'a> super::Command for Joker<'a> {
             Ok(false)
         }
     }
+
+    fn handle_reaction(&mut self, ctx: &Context, rct: &Reaction) -> JoeResult<bool> {
+        self.quote_history
+            .handle_reaction(ctx, rct, &self.messages.read())
+    }
 }
 
 impl<'a> Joker<'a> {
@@ -101,6 +118,7 @@ impl<'a> Joker<'a> {
         top_text: &str,
         bottom_text: &str,
         credits: &str,
+        quoted_idxs: Vec<usize>,
     ) -> JoeResult<()> {
         let time_started = std::time::Instant::now();
 
@@ -109,7 +127,7 @@ impl<'a> Joker<'a> {
 
         let time_render = std::time::Instant::now();
 
-        channel_id.send_files(
+        let posted = channel_id.send_files(
             &ctx.http,
             vec![AttachmentType::Bytes {
                 data: Cow::from(img),
@@ -122,9 +140,15 @@ impl<'a> Joker<'a> {
                     e.footer(|f| f.text(credits));
                     e
                 });
+                if !quoted_idxs.is_empty() {
+                    m.reactions(vec![CONTEXT_REACTION]);
+                }
                 m
             },
         )?;
+        if !quoted_idxs.is_empty() {
+            self.quote_history.push(posted.id, quoted_idxs);
+        }
 
         let time_message = std::time::Instant::now();
         println!(
@@ -165,7 +189,7 @@ fn pick_text<'a>(
     rng: &mut SmallRng,
     min_words: usize,
     str_prompt: Option<&'a str>,
-) -> Option<(&'a Author, &'a str)> {
+) -> Option<&'a crate::messages::Message> {
     let max_len = std::cmp::max(200, 50 + 8 * min_words);
     let len_filter = |m: &&crate::messages::Message| {
         if min_words != 0 {
@@ -180,16 +204,26 @@ fn pick_text<'a>(
     };
 
     let potential_picks = if let Some(ref p) = str_prompt {
-        messages
+        let exact_picks = messages
             .containing_any_words(p)
             .into_iter()
             .filter(len_filter)
-            .collect::<Vec<_>>()
+            .collect::<Vec<_>>();
+        if exact_picks.is_empty() {
+            messages
+                .containing_any_words_fuzzy(p)
+                .into_iter()
+                .filter(len_filter)
+                .collect::<Vec<_>>()
+        } else {
+            exact_picks
+        }
     } else {
-        messages.texts.iter().filter(len_filter).collect::<Vec<_>>()
+        messages
+            .visible_texts()
+            .filter(len_filter)
+            .collect::<Vec<_>>()
     };
 
-    potential_picks
-        .choose(rng)
-        .map(|msg| (&messages.authors[msg.author_idx], msg.text.as_str()))
+    potential_picks.choose(rng).copied()
 }
@@ -0,0 +1,137 @@
+use crate::{
+    config::{Config, UserMatcher},
+    links::DiscordLinks,
+    messages::MessageDump,
+    utils::split_command_rest,
+    JoeResult,
+};
+use circular_queue::CircularQueue;
+use serenity::{model::prelude::*, prelude::*};
+
+const CONFIRM_REACTION: char = '✅';
+
+struct PendingLink {
+    request_id: MessageId,
+    user_id: u64,
+    short_name: String,
+    confirmed: bool,
+}
+
+// A link takes effect once the name's current owner, or an admin if there is none, confirms it
+pub struct Linking<'a> {
+    messages: &'a RwLock<MessageDump>,
+    user_matcher: &'a UserMatcher,
+    admin_ids: &'a [u64],
+    discord_links: &'a RwLock<DiscordLinks>,
+    pending: CircularQueue<PendingLink>,
+}
+
+impl<'a> Linking<'a> {
+    pub fn new(
+        messages: &'a RwLock<MessageDump>,
+        discord_links: &'a RwLock<DiscordLinks>,
+        conf: &'a Config,
+    ) -> Self {
+        Self {
+            messages,
+            user_matcher: &conf.user_matcher,
+            admin_ids: &conf.admin_ids,
+            discord_links,
+            pending: CircularQueue::with_capacity(20),
+        }
+    }
+}
+
+impl<'a> super::Command for Linking<'a> {
+    fn handle_message(&mut self, ctx: &Context, msg: &Message) -> JoeResult<bool> {
+        let user_id = msg.author.id.0;
+        match split_command_rest(msg) {
+            ("!unlink", _) => {
+                let reply = match self.discord_links.write().unlink(user_id)? {
+                    Some(short_name) => format!("Прощай, {}. Теперь ты просто путник.", short_name),
+                    None => "Мы и так не знакомы, приятель.".to_owned(),
+                };
+                msg.channel_id.say(&ctx.http, reply)?;
+                Ok(true)
+            }
+            ("!link", "") => {
+                msg.channel_id
+                    .say(&ctx.http, "Кто ты такой, приятель? `!link денко`")?;
+                Ok(true)
+            }
+            ("!link", name) => {
+                let short_name = {
+                    let messages = self.messages.read();
+                    match self.user_matcher.find_author(&messages.authors, name) {
+                        Some(idx) => messages.authors[idx].short_name.to_owned(),
+                        None => {
+                            msg.channel_id
+                                .say(&ctx.http, format!("{}? Не слыхал о таком.", name))?;
+                            return Ok(true);
+                        }
+                    }
+                };
+
+                let reply = match self.discord_links.read().user_id(&short_name) {
+                    Some(linked) if linked == user_id => {
+                        msg.channel_id
+                            .say(&ctx.http, format!("Да помню я, что ты {}.", short_name))?;
+                        return Ok(true);
+                    }
+                    Some(owner_id) => format!(
+                        "<@{}>, <@{}> называет себя {}. Поставь {}, если уступаешь место.",
+                        owner_id, user_id, short_name, CONFIRM_REACTION
+                    ),
+                    None => format!(
+                        "Назваться {} может любой, приятель. Пусть шериф подтвердит {}.",
+                        short_name, CONFIRM_REACTION
+                    ),
+                };
+                let request = msg.channel_id.say(&ctx.http, reply)?;
+                self.pending.push(PendingLink {
+                    request_id: request.id,
+                    user_id,
+                    short_name,
+                    confirmed: false,
+                });
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn handle_reaction(&mut self, ctx: &Context, rct: &Reaction) -> JoeResult<bool> {
+        match &rct.emoji {
+            ReactionType::Unicode(e) if *e == CONFIRM_REACTION.to_string() => {}
+            _ => return Ok(false),
+        }
+        let pending = match self
+            .pending
+            .iter_mut()
+            .find(|p| p.request_id == rct.message_id)
+        {
+            Some(pending) => pending,
+            None => return Ok(false),
+        };
+        let mut links = self.discord_links.write();
+        let owner_id = links.user_id(&pending.short_name);
+        let confirmed_by_owner = match owner_id {
+            Some(owner_id) => owner_id == rct.user_id.0,
+            None => self.admin_ids.contains(&rct.user_id.0),
+        };
+        if pending.confirmed || !confirmed_by_owner {
+            return Ok(false);
+        }
+
+        if let Some(owner_id) = owner_id {
+            links.unlink(owner_id)?;
+        }
+        links.link(pending.user_id, &pending.short_name)?;
+        pending.confirmed = true;
+        rct.channel_id.say(
+            &ctx.http,
+            format!("Добро пожаловать обратно в салун, {}.", pending.short_name),
+        )?;
+        Ok(true)
+    }
+}
@@ -0,0 +1,164 @@
+use super::quote::{QuoteHistory, CONTEXT_REACTION};
+use crate::{config::Config, messages::MessageDump, JoeResult};
+use chrono::{Datelike, Local, NaiveDate, NaiveDateTime, NaiveTime};
+use rand::{rngs::SmallRng, seq::SliceRandom, SeedableRng};
+use serenity::{http::Http, model::prelude::*, prelude::*};
+use std::collections::BTreeMap;
+
+const MAX_YEARS_SHOWN: usize = 5;
+const MEMORY_TEXT_LIMIT: usize = 300;
+
+pub struct Memories<'a> {
+    messages: &'a RwLock<MessageDump>,
+    channel_id: ChannelId,
+    daily_post_at: Option<NaiveTime>,
+    last_posted_on: Option<NaiveDate>,
+    rng: SmallRng,
+    quote_history: QuoteHistory,
+}
+
+impl<'a> Memories<'a> {
+    pub fn new(
+        messages: &'a RwLock<MessageDump>,
+        conf: &Config,
+        channel_id: u64,
+    ) -> JoeResult<Self> {
+        let daily_post_at = match conf.daily_memory_at {
+            Some(ref time) => Some(
+                NaiveTime::parse_from_str(time, "%H:%M")
+                    .map_err(|e| format!("daily_memory_at: {}", e))?,
+            ),
+            None => None,
+        };
+        // Don't post again if the bot is restarted later in the day
+        let now = Local::now().naive_local();
+        let last_posted_on = daily_post_at
+            .filter(|&at| now.time() >= at)
+            .map(|_| now.date());
+
+        Ok(Self {
+            messages,
+            channel_id: ChannelId(channel_id),
+            daily_post_at,
+            last_posted_on,
+            rng: SmallRng::from_entropy(),
+            quote_history: QuoteHistory::new(),
+        })
+    }
+}
+
+impl<'a> super::Command for Memories<'a> {
+    fn handle_message(&mut self, ctx: &Context, msg: &Message) -> JoeResult<bool> {
+        if msg.content != "!вэтотдень" {
+            return Ok(false);
+        }
+
+        let today = Local::now().naive_local().date();
+        let messages = self.messages.read();
+        let picks = memories_on(&messages, today)
+            .into_iter()
+            .rev()
+            .take(MAX_YEARS_SHOWN)
+            .filter_map(|(year, idxs)| idxs.choose(&mut self.rng).map(|&idx| (year, idx)))
+            .collect::<Vec<_>>();
+        if picks.is_empty() {
+            msg.channel_id
+                .say(&ctx.http, "В этот день в салуне было тихо.")?;
+            return Ok(true);
+        }
+
+        let posted = msg.channel_id.send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.color(crate::EMBED_COLOR);
+                e.title(format!("В этот день, {}", today.format("%d.%m")));
+                for &(year, idx) in &picks {
+                    e.field(year, memory_line(&messages, idx), false);
+                }
+                e
+            });
+            m.reactions(vec![CONTEXT_REACTION]);
+            m
+        })?;
+        self.quote_history
+            .push(posted.id, picks.iter().map(|&(_, idx)| idx).collect());
+
+        Ok(true)
+    }
+
+    fn handle_reaction(&mut self, ctx: &Context, rct: &Reaction) -> JoeResult<bool> {
+        self.quote_history
+            .handle_reaction(ctx, rct, &self.messages.read())
+    }
+
+    fn handle_tick(&mut self, http: &Http, now: NaiveDateTime) -> JoeResult<()> {
+        match self.daily_post_at {
+            Some(at) if now.time() >= at && self.last_posted_on != Some(now.date()) => {}
+            _ => return Ok(()),
+        }
+        let messages = self.messages.read();
+        let idxs = memories_on(&messages, now.date())
+            .into_values()
+            .flatten()
+            .collect::<Vec<_>>();
+        let idx = match idxs.choose(&mut self.rng) {
+            Some(&idx) => idx,
+            None => {
+                self.last_posted_on = Some(now.date());
+                return Ok(());
+            }
+        };
+
+        let posted = self.channel_id.send_message(http, |m| {
+            m.embed(|e| {
+                e.color(crate::EMBED_COLOR);
+                e.title(format!(
+                    "В этот день в {} году",
+                    messages.texts[idx].date.year()
+                ));
+                e.description(memory_line(&messages, idx));
+                e
+            });
+            m.reactions(vec![CONTEXT_REACTION]);
+            m
+        })?;
+        // Not until it's posted, so that a failed post is retried on the next tick
+        self.last_posted_on = Some(now.date());
+        self.quote_history.push(posted.id, vec![idx]);
+
+        Ok(())
+    }
+}
+
+fn memories_on(messages: &MessageDump, date: NaiveDate) -> BTreeMap<i32, Vec<usize>> {
+    let mut by_year: BTreeMap<i32, Vec<usize>> = BTreeMap::new();
+    for (idx, m) in messages.texts.iter().enumerate() {
+        if m.continuation
+            || m.date.year() >= date.year()
+            || m.date.month() != date.month()
+            || m.date.day() != date.day()
+            || !messages.is_visible(m)
+        {
+            continue;
+        }
+        by_year.entry(m.date.year()).or_default().push(idx);
+    }
+    by_year
+}
+
+fn memory_line(messages: &MessageDump, idx: usize) -> String {
+    let message = messages.full_message(idx);
+    let mut text = message
+        .text
+        .chars()
+        .take(MEMORY_TEXT_LIMIT)
+        .collect::<String>();
+    if text.len() < message.text.len() {
+        text.push('…');
+    }
+    format!(
+        "`{}` **{}**: {}",
+        message.date.format("%H:%M"),
+        messages.authors[message.author_idx].short_name,
+        text
+    )
+}
@@ -1,55 +1,113 @@
 use crate::JoeResult;
+use chrono::NaiveDateTime;
 use serenity::{
     client::Context,
-    model::channel::{Message, Reaction},
+    http::Http,
+    model::{
+        channel::{Message, Reaction},
+        id::ChannelId,
+    },
 };
+use std::collections::HashMap;
 
+mod activity;
 mod chain;
+mod grep;
 mod img2msg;
 mod joker;
+mod linking;
+mod memories;
 mod poll;
+mod privacy;
+mod quote;
+mod signature_words;
 mod taki;
 mod wdyt;
+mod word_cloud;
+mod word_stats;
+mod wrapped;
 
+pub use activity::Activity;
 pub use chain::Chain;
+pub use grep::Grep;
 pub use img2msg::Img2msg;
 pub use joker::Joker;
+pub use linking::Linking;
+pub use memories::Memories;
 pub use poll::Poll;
+pub use privacy::Privacy;
+pub use signature_words::SignatureWords;
 pub use taki::Taki;
 pub use wdyt::Wdyt;
+pub use word_cloud::WordCloud;
+pub use word_stats::WordStats;
+pub use wrapped::Wrapped;
 
 pub trait Command {
     fn handle_message(&mut self, ctx: &Context, msg: &Message) -> JoeResult<bool>;
 
+    // Called with messages that no command has handled
+    fn handle_unclaimed_message(&mut self, _ctx: &Context, _msg: &Message) -> JoeResult<bool> {
+        Ok(false)
+    }
+
     fn handle_reaction(&mut self, _ctx: &Context, _rct: &Reaction) -> JoeResult<bool> {
         Ok(false)
     }
+
+    // Called by the scheduler about once a minute
+    fn handle_tick(&mut self, _http: &Http, _now: NaiveDateTime) -> JoeResult<()> {
+        Ok(())
+    }
 }
 
+pub type Commands<'a> = Vec<Box<dyn Command + 'a + Send + Sync>>;
+
+// Shared commands are tried first, whatever the channel
 pub struct CommandDispatcher<'a> {
-    commands: Vec<Box<dyn Command + 'a + Send + Sync>>,
+    shared: Commands<'a>,
+    channels: HashMap<ChannelId, Commands<'a>>,
 }
 
 impl<'a> CommandDispatcher<'a> {
-    pub fn new(commands: Vec<Box<dyn Command + 'a + Send + Sync>>) -> Self {
-        Self { commands }
+    pub fn new(shared: Commands<'a>, channels: HashMap<ChannelId, Commands<'a>>) -> Self {
+        Self { shared, channels }
     }
 
     pub fn handle_message(&mut self, ctx: &Context, msg: &Message) -> JoeResult<bool> {
-        for cmd in &mut self.commands {
-            if cmd.handle_message(ctx, msg)? {
-                return Ok(true);
+        if let Some(commands) = self.channels.get_mut(&msg.channel_id) {
+            for cmd in self.shared.iter_mut().chain(commands.iter_mut()) {
+                if cmd.handle_message(ctx, msg)? {
+                    return Ok(true);
+                }
+            }
+            for cmd in self.shared.iter_mut().chain(commands) {
+                if cmd.handle_unclaimed_message(ctx, msg)? {
+                    return Ok(true);
+                }
             }
         }
         Ok(false)
     }
 
     pub fn handle_reaction(&mut self, ctx: &Context, rct: &Reaction) -> JoeResult<bool> {
-        for cmd in &mut self.commands {
-            if cmd.handle_reaction(ctx, rct)? {
-                return Ok(true);
+        if let Some(commands) = self.channels.get_mut(&rct.channel_id) {
+            for cmd in self.shared.iter_mut().chain(commands) {
+                if cmd.handle_reaction(ctx, rct)? {
+                    return Ok(true);
+                }
             }
         }
         Ok(false)
     }
+
+    // A failing command doesn't keep the others from their tick
+    pub fn handle_tick(&mut self, http: &Http, now: NaiveDateTime) {
+        let channel_commands = self.channels.values_mut().flatten();
+        for cmd in self.shared.iter_mut().chain(channel_commands) {
+            if let Err(e) = cmd.handle_tick(http, now) {
+                eprintln!("Scheduler: {}", e);
+            }
+        }
+    }
 }
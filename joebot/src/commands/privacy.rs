@@ -0,0 +1,74 @@
+use crate::{config::Config, links::DiscordLinks, messages::MessageDump, storage, JoeResult};
+use serenity::{model::prelude::*, prelude::*};
+
+const KEY_OPTED_OUT: &str = "optedout";
+
+pub struct Privacy<'a> {
+    messages: &'a RwLock<MessageDump>,
+    discord_links: &'a RwLock<DiscordLinks>,
+    storage: storage::ChatGameStorage,
+}
+
+impl<'a> Privacy<'a> {
+    pub fn new(
+        messages: &'a RwLock<MessageDump>,
+        discord_links: &'a RwLock<DiscordLinks>,
+        conf: &Config,
+        redis: &storage::Redis,
+    ) -> JoeResult<Self> {
+        let mut storage = redis.get_game_storage("privacy", conf.channel_id);
+
+        let links = discord_links.read();
+        let mut dump = messages.write();
+        for (user_id, _) in storage.fetch_sorted_set(KEY_OPTED_OUT)? {
+            if let Some(short_name) = links.short_name(user_id) {
+                dump.set_opted_out(short_name, true);
+            }
+        }
+        drop(dump);
+        drop(links);
+
+        Ok(Self {
+            messages,
+            discord_links,
+            storage,
+        })
+    }
+}
+
+impl<'a> super::Command for Privacy<'a> {
+    fn handle_message(&mut self, ctx: &Context, msg: &Message) -> JoeResult<bool> {
+        let opt_out = match msg.content.as_str() {
+            "!optout" => true,
+            "!optin" => false,
+            _ => return Ok(false),
+        };
+        let links = self.discord_links.read();
+        let short_name = match links.short_name(msg.author.id.0) {
+            Some(name) => name,
+            None => {
+                msg.channel_id.say(
+                    &ctx.http,
+                    "Не припомню, чтобы мы встречались, приятель. Представься: `!link имя`",
+                )?;
+                return Ok(true);
+            }
+        };
+
+        if opt_out {
+            self.storage
+                .add_gt_to_set(KEY_OPTED_OUT, msg.author.id.0, 1)?;
+        } else {
+            self.storage.rem_from_set(KEY_OPTED_OUT, msg.author.id.0)?;
+        }
+        self.messages.write().set_opted_out(short_name, opt_out);
+
+        let reply = if opt_out {
+            "Твои слова останутся между нами. Вернуться — `!optin`"
+        } else {
+            "С возвращением в салун, ковбой."
+        };
+        msg.channel_id.say(&ctx.http, reply)?;
+        Ok(true)
+    }
+}
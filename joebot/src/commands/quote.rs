@@ -0,0 +1,97 @@
+use crate::{messages::MessageDump, JoeResult};
+use circular_queue::CircularQueue;
+use serenity::{model::prelude::*, prelude::*};
+use std::borrow::Cow;
+use std::fmt::Write;
+
+pub const CONTEXT_REACTION: char = '📜';
+
+const CONTEXT_RADIUS: usize = 3;
+const CONTEXT_TEXT_LIMIT: usize = 250;
+
+pub struct QuoteHistory {
+    quotes: CircularQueue<(MessageId, Vec<usize>)>,
+}
+
+impl QuoteHistory {
+    pub fn new() -> Self {
+        Self {
+            quotes: CircularQueue::with_capacity(100),
+        }
+    }
+
+    pub fn push(&mut self, posted_id: MessageId, quoted_idxs: Vec<usize>) {
+        self.quotes.push((posted_id, quoted_idxs));
+    }
+
+    pub fn handle_reaction(
+        &self,
+        ctx: &Context,
+        rct: &Reaction,
+        messages: &MessageDump,
+    ) -> JoeResult<bool> {
+        match &rct.emoji {
+            ReactionType::Unicode(e) if *e == CONTEXT_REACTION.to_string() => {
+                let quoted_idxs = match self.quotes.iter().find(|(mid, _)| *mid == rct.message_id) {
+                    Some((_, idxs)) => idxs,
+                    None => return Ok(false),
+                };
+                for &idx in quoted_idxs {
+                    let (title, description) = quote_context(messages, idx)?;
+                    rct.channel_id.send_message(&ctx.http, |m| {
+                        m.embed(|e| {
+                            e.color(crate::EMBED_COLOR);
+                            e.title(title);
+                            e.description(description);
+                            e
+                        });
+                        m
+                    })?;
+                }
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+fn quote_context(messages: &MessageDump, idx: usize) -> JoeResult<(String, String)> {
+    let context = messages.surrounding(idx, CONTEXT_RADIUS);
+    let title = messages.texts[idx].date.format("%d.%m.%Y").to_string();
+
+    let mut description = String::new();
+    for msg in context {
+        if !messages.is_visible(&messages.texts[msg.parts.start]) {
+            continue;
+        }
+        let author = &messages.authors[msg.author_idx];
+        let time = msg.date.format("%H:%M");
+        if msg.parts.contains(&idx) {
+            // Long messages are shown starting from the quoted part
+            let text = truncate(&messages.texts[idx].text);
+            let before = if idx > msg.parts.start { "…" } else { "" };
+            let after = if idx + 1 < msg.parts.end { "…" } else { "" };
+            writeln!(
+                &mut description,
+                "`{}` **{}: {}{}{}**",
+                time, author.short_name, before, text, after
+            )?;
+        } else {
+            writeln!(
+                &mut description,
+                "`{}` {}: {}",
+                time,
+                author.short_name,
+                truncate(&msg.text)
+            )?;
+        }
+    }
+    Ok((title, description))
+}
+
+fn truncate(text: &str) -> Cow<'_, str> {
+    match text.char_indices().nth(CONTEXT_TEXT_LIMIT) {
+        Some((end, _)) => Cow::Owned(format!("{}…", &text[..end])),
+        None => Cow::Borrowed(text),
+    }
+}
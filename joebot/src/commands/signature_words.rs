@@ -0,0 +1,99 @@
+use crate::{
+    config::UserMatcher, links::DiscordLinks, messages::MessageDump, utils::split_command_rest,
+    JoeResult,
+};
+use serenity::{model::prelude::*, prelude::*};
+use std::fmt::Write;
+
+const NUM_WORDS: usize = 15;
+
+pub struct SignatureWords<'a> {
+    messages: &'a RwLock<MessageDump>,
+    user_matcher: &'a UserMatcher,
+    discord_links: &'a RwLock<DiscordLinks>,
+}
+
+impl<'a> SignatureWords<'a> {
+    pub fn new(
+        messages: &'a RwLock<MessageDump>,
+        user_matcher: &'a UserMatcher,
+        discord_links: &'a RwLock<DiscordLinks>,
+    ) -> Self {
+        Self {
+            messages,
+            user_matcher,
+            discord_links,
+        }
+    }
+}
+
+impl<'a> super::Command for SignatureWords<'a> {
+    fn handle_message(&mut self, ctx: &Context, msg: &Message) -> JoeResult<bool> {
+        let (command, name) = split_command_rest(msg);
+        if command != "!словарь" {
+            return Ok(false);
+        }
+
+        let messages = self.messages.read();
+        let author_idx = if name.is_empty() {
+            let linked_idx = self
+                .discord_links
+                .read()
+                .author_idx(&messages.authors, msg.author.id.0);
+            match linked_idx {
+                Some(idx) => Some(idx),
+                None => {
+                    msg.channel_id.say(
+                        &ctx.http,
+                        "Чей словарь, приятель? `!словарь денко`, а свой — после `!link имя`",
+                    )?;
+                    return Ok(true);
+                }
+            }
+        } else {
+            self.user_matcher.find_author(&messages.authors, name)
+        };
+        let author = author_idx
+            .map(|idx| (idx, &messages.authors[idx]))
+            .filter(|(_, a)| !messages.opted_out().contains(&a.short_name));
+        let (author_idx, author) = match author {
+            Some(author) => author,
+            None => {
+                msg.channel_id
+                    .say(&ctx.http, format!("{}? Не слыхал о таком.", name))?;
+                return Ok(true);
+            }
+        };
+
+        let words = messages.signature_words(author_idx, NUM_WORDS);
+        if words.is_empty() {
+            msg.channel_id.say(
+                &ctx.http,
+                format!(
+                    "{} говорит как все в салуне, ничего особенного.",
+                    author.short_name
+                ),
+            )?;
+            return Ok(true);
+        }
+
+        let mut description = String::new();
+        for w in &words {
+            writeln!(
+                &mut description,
+                "**{}** — {} раз, в {:.1} раза чаще остальных",
+                w.word, w.uses, w.ratio
+            )?;
+        }
+        msg.channel_id.send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.color(crate::EMBED_COLOR);
+                e.title(format!("Словарь {}", author.short_name));
+                e.description(description);
+                e
+            })
+        })?;
+
+        Ok(true)
+    }
+}
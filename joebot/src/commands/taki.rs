@@ -1,18 +1,28 @@
 use crate::{
     config::{Config, UserMatcher},
+    links::DiscordLinks,
     messages::{Author, MessageDump},
-    storage, JoeResult,
+    storage,
+    utils::split_command_rest,
+    JoeResult,
 };
+use chrono::{Duration, Local, NaiveDateTime};
 use rand::{rngs::SmallRng, seq::SliceRandom, SeedableRng};
-use serenity::{model::prelude::*, prelude::*};
+use serde::{Deserialize, Serialize};
+use serenity::{http::Http, model::prelude::*, prelude::*};
 use std::collections::BTreeMap;
 use std::fmt::Write;
 
 mod picker;
-use picker::SuspectPicker;
+mod rating;
+mod season;
+use picker::{SuspectPicker, SuspectText};
+use season::StatsView;
 
 const MAX_TRIES: usize = 5;
-const MESSAGES_SHOWN: usize = 3;
+const FIRST_HINT_AFTER: usize = 2; // wrong answers
+const HINT_PENALTY: usize = 1;
+const SELF_RECOGNITION_BONUS: u32 = 2;
 const START_MESSAGES: [(&str, &str); 3] = [
     ("Один мудрец сказал:", "Кто же это был?"),
     (
@@ -33,6 +43,10 @@ const LOSE_MESSAGES: [&str; 4] = [
     "Очень жаль, но вы проиграли... Жалкие псы!",
     "Удачи в другой раз, амигос.",
 ];
+const TIMEOUT_MESSAGES: [&str; 2] = [
+    "Солнце село, а подозреваемый так и ушел безнаказанным.",
+    "Пока вы думали, он уже скрылся за горизонтом.",
+];
 
 const KEY_BEST_STREAK: &str = "streaks";
 const KEY_CURR_STREAK: &str = "currstreak";
@@ -41,45 +55,370 @@ const KEY_SCORE: &str = "scores";
 const KEY_NUM_TRIES: &str = "numtries";
 const KEY_NUM_WINS: &str = "numwins";
 
+const KEY_RATING: &str = "ratings";
+const KEY_RATING_HISTORY: &str = "ratinghistory"; // + ":<uid>"
+const SEASON_WINNERS: usize = 3;
+const RATING_HISTORY_LEN: isize = 10;
+
+const KEY_ONGOING: &str = "ongoing";
+const KEY_PICK_HISTORY: &str = "pickhistory";
+
+pub struct PlayerRecord {
+    pub score: i32,
+    pub best_streak: i32,
+    pub num_tries: i32,
+    pub num_wins: i32,
+}
+
+impl PlayerRecord {
+    pub fn fetch(storage: &mut storage::ChatGameStorage, uid: u64) -> JoeResult<Self> {
+        Ok(Self {
+            score: season::all_time_score(storage, KEY_SCORE, uid)?,
+            best_streak: season::all_time_score(storage, KEY_BEST_STREAK, uid)?,
+            num_tries: season::all_time_score(storage, KEY_NUM_TRIES, uid)?,
+            num_wins: season::all_time_score(storage, KEY_NUM_WINS, uid)?,
+        })
+    }
+}
+
 pub struct Taki<'a> {
     suspect_picker: SuspectPicker<'a>,
     suspect_matcher: &'a UserMatcher,
+    discord_links: &'a RwLock<DiscordLinks>,
     storage: storage::ChatGameStorage,
-    ongoing: Option<OngoingGame<'a>>,
+    channel_id: ChannelId,
+    timeout: Option<Duration>,
+    season_length: Option<Duration>,
+    admin_ids: &'a [u64],
+    ongoing: Option<OngoingGame>,
     rng: SmallRng,
 }
 
-struct OngoingGame<'a> {
-    suspect: &'a Author,
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+#[derive(Serialize, Deserialize)]
+struct OngoingGame {
+    suspect: Author,
+    difficulty: Difficulty,
+    started_at: NaiveDateTime,
     answers: Vec<UserId>,
+    hints: Vec<String>,
+    hints_given: usize,
 }
 
 impl<'a> Taki<'a> {
-    pub fn new(messages: &'a MessageDump, conf: &'a Config, redis: &storage::Redis) -> Self {
-        let suspect_picker = SuspectPicker::new(messages, &conf.user_penalties);
+    pub fn new(
+        messages: &'a RwLock<MessageDump>,
+        discord_links: &'a RwLock<DiscordLinks>,
+        conf: &'a Config,
+        channel_id: u64,
+        redis: &storage::Redis,
+    ) -> JoeResult<Self> {
+        let mut suspect_picker = SuspectPicker::new(
+            messages,
+            &conf.user_penalties,
+            conf.taki_conversation.as_deref(),
+        );
+        let mut storage = redis.get_game_storage("taki", channel_id);
+
+        // Pick up where we left off before the restart
+        if let Some(history) = storage.fetch_value(KEY_PICK_HISTORY)? {
+            suspect_picker.restore_history(serde_json::from_str(&history)?);
+        }
+        let ongoing = match storage.fetch_value(KEY_ONGOING)? {
+            Some(game) => Some(serde_json::from_str(&game)?),
+            None => None,
+        };
 
-        Self {
+        Ok(Self {
             suspect_picker,
             suspect_matcher: &conf.user_matcher,
-            storage: redis.get_game_storage("taki", conf.channel_id),
-            ongoing: None,
+            discord_links,
+            storage,
+            channel_id: ChannelId(channel_id),
+            timeout: match conf.taki_timeout_mins {
+                0 => None,
+                mins => Some(Duration::minutes(mins as i64)),
+            },
+            season_length: match conf.taki_season_days {
+                0 => None,
+                days => Some(Duration::days(days as i64)),
+            },
+            admin_ids: &conf.admin_ids,
+            ongoing,
             rng: SmallRng::from_entropy(),
+        })
+    }
+
+    fn save_game(&mut self) -> JoeResult<()> {
+        match self.ongoing {
+            Some(ref game) => self
+                .storage
+                .store_value(KEY_ONGOING, &serde_json::to_string(game)?)?,
+            None => self.storage.remove_value(KEY_ONGOING)?,
         }
+        Ok(())
+    }
+
+    fn guess(
+        &mut self,
+        ctx: &Context,
+        msg: &Message,
+        explicit_guess: Option<&str>,
+    ) -> JoeResult<bool> {
+        let game = match self.ongoing {
+            Some(ref mut game) => game,
+            None => return Ok(false),
+        };
+        // Only names, mentions of linked users and explicit guesses count as answers,
+        // everything else is just chatter
+        let text = explicit_guess.unwrap_or(&msg.content).to_lowercase();
+        let suspect_name = &game.suspect.short_name;
+
+        let links = self.discord_links.read();
+        let mentioned = msg.mentions.iter().find_map(|u| links.short_name(u.id.0));
+        let guessed = match mentioned {
+            Some(short_name) => short_name == suspect_name,
+            None if explicit_guess.is_some() || self.suspect_matcher.matches_any(&text) => {
+                self.suspect_matcher.matches_short_name(&text, suspect_name)
+            }
+            None => return Ok(false),
+        };
+        let recognized_self = links.short_name(msg.author.id.0) == Some(suspect_name);
+        drop(links);
+
+        let uid: u64 = msg.author.id.0;
+        self.storage.incr_in_set(KEY_NUM_TRIES, uid, 1)?;
+
+        game.answers.push(msg.author.id);
+
+        if guessed {
+            self.storage.incr_in_set(KEY_NUM_WINS, uid, 1)?;
+
+            let score: u32 = (MAX_TRIES + 1 - game.answers.len())
+                .saturating_sub(game.hints_given * HINT_PENALTY)
+                .max(1) as u32;
+            let mut score = game.difficulty.weigh_score(score);
+            if recognized_self {
+                score += SELF_RECOGNITION_BONUS;
+            }
+            self.storage.incr_in_set(KEY_SCORE, uid, score as i32)?;
+
+            for answer_uid in &game.answers {
+                if answer_uid.0 != uid {
+                    self.storage.rem_from_set(KEY_CURR_STREAK, answer_uid.0)?;
+                }
+            }
+            update_ratings(&mut self.storage, game, Some(msg.author.id))?;
+
+            // Guessed on the first try?
+            let curr_streak = if game.answers.iter().filter(|&&a| a == msg.author.id).count() == 1 {
+                self.storage.incr_in_set(KEY_CURR_STREAK, uid, 1)?
+            } else {
+                self.storage.rem_from_set(KEY_CURR_STREAK, uid)?;
+                0
+            };
+
+            self.storage
+                .add_gt_to_set(KEY_BEST_STREAK, uid, curr_streak)?;
+            let best_streak = self.storage.get_in_set(KEY_BEST_STREAK, uid)?;
+
+            let (title, score_msg) = match curr_streak {
+                0 | 1 => {
+                    let title = WIN_MESSAGES.choose(&mut self.rng).unwrap();
+                    (title, format!("{} получает +{}", msg.author.name, score))
+                }
+                _ => {
+                    let hits_word = if curr_streak % 100 > 4 && curr_streak % 100 < 20 {
+                        "попаданий"
+                    } else {
+                        match curr_streak % 10 {
+                            1 => "попадание",
+                            2 | 3 | 4 => "попадания",
+                            _ => "попаданий",
+                        }
+                    };
+                    let msg = if curr_streak < best_streak {
+                        format!(
+                            "{} пришпорил коня и понесся вперед! +{} и {} {} подряд в кармане",
+                            msg.author.name, score, curr_streak, hits_word
+                        )
+                    } else {
+                        format!("{} сегодня определенно в ударе! {} {} подряд, я чуть из седла не выпал, когда услыхал! Забирай свои +{} и мчись вперед", msg.author.name, curr_streak, hits_word, score)
+                    };
+                    let title = WIN_STREAK_MESSAGES.choose(&mut self.rng).unwrap();
+                    (title, msg)
+                }
+            };
+
+            let mut resp = format!(
+                "Это был _{}_ под псевдонимом `{}`\n\n{}",
+                game.suspect.full_name, game.suspect.short_name, score_msg
+            );
+            if recognized_self {
+                write!(
+                    &mut resp,
+                    "\n\nНе каждый узнает себя в зеркале салуна, тут и +{} сверху",
+                    SELF_RECOGNITION_BONUS
+                )?;
+            }
+            self.ongoing = None;
+            self.save_game()?;
+
+            msg.channel_id.send_message(&ctx.http, |m| {
+                m.embed(|e| {
+                    e.color(crate::EMBED_COLOR);
+                    e.title(title);
+                    e.description(resp);
+                    e
+                });
+                m
+            })?;
+        } else if game.answers.len() == MAX_TRIES {
+            let title = LOSE_MESSAGES.choose(&mut self.rng).unwrap();
+            self.end_lost_game(&ctx.http, msg.channel_id, title)?;
+        } else {
+            let hint = if game.answers.len() >= FIRST_HINT_AFTER + game.hints_given {
+                game.hints.get(game.hints_given).cloned()
+            } else {
+                None
+            };
+            if hint.is_some() {
+                game.hints_given += 1;
+            }
+            self.save_game()?;
+            if let Some(hint) = hint {
+                msg.channel_id.send_message(&ctx.http, |m| {
+                    m.embed(|e| {
+                        e.color(crate::EMBED_COLOR);
+                        e.title("Подсказка для слепых котят");
+                        e.description(hint);
+                        e
+                    });
+                    m
+                })?;
+            }
+        }
+        Ok(true)
+    }
+
+    fn close_season(&mut self, http: &Http, now: NaiveDateTime) -> JoeResult<()> {
+        let season = season::current_season(&mut self.storage)?;
+        let scores = season::fetch_leaderboard(&mut self.storage, KEY_SCORE, StatsView::Current)?;
+
+        let mut resp = String::new();
+        if scores.is_empty() {
+            writeln!(&mut resp, "Никто так и не взялся за револьвер.")?;
+        } else {
+            writeln!(&mut resp, "Лучшие стрелки сезона:")?;
+            for (index, (uid, score)) in scores.into_iter().take(SEASON_WINNERS).enumerate() {
+                writeln!(&mut resp, "{}) <@{}> — {}", index + 1, uid, score)?;
+            }
+        }
+        write!(
+            &mut resp,
+            "\nСезон {} открыт, счет обнулен. Старые записи — `!takistats {}`",
+            season + 1,
+            season
+        )?;
+
+        self.channel_id.send_message(http, |m| {
+            m.embed(|e| {
+                e.color(crate::EMBED_COLOR);
+                e.title(format!("Сезон {} окончен", season));
+                e.description(resp);
+                e
+            });
+            m
+        })?;
+
+        // Only once everyone's heard, so that a failed announcement is retried on the next tick
+        season::end_season(&mut self.storage, now)?;
+        Ok(())
+    }
+
+    fn end_lost_game(&mut self, http: &Http, channel_id: ChannelId, title: &str) -> JoeResult<()> {
+        let resp = match self.ongoing {
+            Some(ref game) => format!(
+                "Это был _{}_ под псевдонимом `{}`",
+                game.suspect.full_name, game.suspect.short_name
+            ),
+            None => return Ok(()),
+        };
+        // Reveal first, so that the game is still there to retry if Discord fails us
+        channel_id.send_message(http, |m| {
+            m.embed(|e| {
+                e.color(crate::EMBED_COLOR);
+                e.title(title);
+                e.description(resp);
+                e
+            });
+            m
+        })?;
+
+        let game = self.ongoing.take().unwrap();
+        self.save_game()?;
+        // Your streaks end here, partners. Easy come, easy go...
+        for uid in &game.answers {
+            self.storage.rem_from_set(KEY_CURR_STREAK, uid.0)?;
+        }
+        update_ratings(&mut self.storage, &game, None)?;
+        Ok(())
     }
 }
 
 impl<'a> super::Command for Taki<'a> {
     fn handle_message(&mut self, ctx: &Context, msg: &Message) -> JoeResult<bool> {
-        match (msg.content.as_str(), &mut self.ongoing) {
-            ("!takistart", None) => {
-                let (suspect, messages) = self
-                    .suspect_picker
-                    .random_suspect(&mut self.rng, MESSAGES_SHOWN);
+        match (split_command_rest(msg), &mut self.ongoing) {
+            (("!takistart", level), None) => {
+                let difficulty = match Difficulty::parse(level) {
+                    Some(difficulty) => difficulty,
+                    None => {
+                        msg.channel_id.say(
+                            &ctx.http,
+                            "Выбирай по себе, ковбой: `!takistart easy`, `normal` или `hard`",
+                        )?;
+                        return Ok(true);
+                    }
+                };
+
+                // One more text is kept for a hint
+                let num_shown = difficulty.texts_shown();
+                let picked =
+                    self.suspect_picker
+                        .random_suspect(&mut self.rng, num_shown + 1, difficulty);
+                let (suspect, mut texts) = match picked {
+                    Some(picked) => picked,
+                    None => {
+                        msg.channel_id
+                            .say(&ctx.http, "Некого подозревать, ковбой: салун пустой")?;
+                        return Ok(true);
+                    }
+                };
+                let hint_text = if texts.len() > num_shown {
+                    texts.pop()
+                } else {
+                    None
+                };
+                let hints = game_hints(&suspect, &texts, hint_text);
+                let messages = texts.into_iter().map(|t| t.text).collect::<Vec<_>>();
 
                 self.ongoing = Some(OngoingGame {
                     suspect,
+                    difficulty,
+                    started_at: Local::now().naive_local(),
                     answers: Vec::with_capacity(MAX_TRIES),
+                    hints,
+                    hints_given: 0,
                 });
+                self.save_game()?;
+                let history = serde_json::to_string(self.suspect_picker.history())?;
+                self.storage.store_value(KEY_PICK_HISTORY, &history)?;
                 let (start_prefix, start_suffix) = START_MESSAGES.choose(&mut self.rng).unwrap();
 
                 let resp = format!("* {}\n\n{}", messages.join("\n* "), start_suffix);
@@ -96,12 +435,43 @@ impl<'a> super::Command for Taki<'a> {
 
                 Ok(true)
             }
-            ("!takistats", _) => {
+            (("!takiseason", _), _) => {
+                if !self.admin_ids.contains(&msg.author.id.0) {
+                    msg.channel_id
+                        .say(&ctx.http, "Закрывать сезон может только шериф, приятель.")?;
+                    return Ok(true);
+                }
+                self.close_season(&ctx.http, Local::now().naive_local())?;
+                Ok(true)
+            }
+            (("!takistats", arg), _) => {
+                let current_season = season::current_season(&mut self.storage)?;
+                let view = match StatsView::parse(arg) {
+                    Some(StatsView::Season(s)) if s == current_season => StatsView::Current,
+                    Some(StatsView::Season(s)) if s == 0 || s > current_season => {
+                        msg.channel_id.say(
+                            &ctx.http,
+                            format!(
+                                "Сезона {} еще не было, а сейчас идет {}-й.",
+                                s, current_season
+                            ),
+                        )?;
+                        return Ok(true);
+                    }
+                    Some(view) => view,
+                    None => {
+                        msg.channel_id.say(
+                            &ctx.http,
+                            "`!takistats` — текущий сезон, `!takistats N` — сезон N, `!takistats all` — за все время",
+                        )?;
+                        return Ok(true);
+                    }
+                };
                 let mut stats = String::new();
 
                 let mut user_cache: BTreeMap<u64, User> = BTreeMap::new();
 
-                let scores = self.storage.fetch_sorted_set(KEY_SCORE)?;
+                let scores = season::fetch_leaderboard(&mut self.storage, KEY_SCORE, view)?;
                 writeln!(&mut stats, "Самые именитые стрелки:")?;
                 for (index, (uid, score)) in scores.into_iter().enumerate() {
                     if !user_cache.contains_key(&uid) {
@@ -116,7 +486,37 @@ impl<'a> super::Command for Taki<'a> {
                     )?;
                 }
 
-                let streaks = self.storage.fetch_sorted_set(KEY_BEST_STREAK)?;
+                // Skill doesn't start over with the seasons, so it isn't archived
+                let ratings = match view {
+                    StatsView::Season(_) => Vec::new(),
+                    _ => self.storage.fetch_sorted_set(KEY_RATING)?,
+                };
+                if !ratings.is_empty() {
+                    writeln!(&mut stats, "\nСамые искусные стрелки:")?;
+                }
+                for (index, (uid, rating)) in ratings.into_iter().enumerate() {
+                    if !user_cache.contains_key(&uid) {
+                        user_cache.insert(uid, UserId(uid).to_user(ctx)?);
+                    }
+                    let history = self
+                        .storage
+                        .fetch_list(&format!("{}:{}", KEY_RATING_HISTORY, uid))?
+                        .iter()
+                        .map(|r| r.parse())
+                        .collect::<Result<Vec<i32>, _>>()?;
+                    let change = rating - history.first().copied().unwrap_or(rating);
+                    writeln!(
+                        &mut stats,
+                        "{}) {} — {} {} ({:+})",
+                        index + 1,
+                        user_cache[&uid].name,
+                        rating,
+                        rating::sparkline(&history),
+                        change
+                    )?;
+                }
+
+                let streaks = season::fetch_leaderboard(&mut self.storage, KEY_BEST_STREAK, view)?;
                 writeln!(&mut stats, "\nСамые удачливые стрелки:")?;
                 for (index, (uid, streak)) in streaks.into_iter().enumerate() {
                     if streak < 1 {
@@ -134,8 +534,8 @@ impl<'a> super::Command for Taki<'a> {
                     )?;
                 }
 
-                let tries = self.storage.fetch_sorted_set(KEY_NUM_TRIES)?;
-                let wins = self.storage.fetch_sorted_set(KEY_NUM_WINS)?;
+                let tries = season::fetch_leaderboard(&mut self.storage, KEY_NUM_TRIES, view)?;
+                let wins = season::fetch_leaderboard(&mut self.storage, KEY_NUM_WINS, view)?;
 
                 let mut kdratios: Vec<(u64, u32)> = wins
                     .iter()
@@ -165,7 +565,11 @@ impl<'a> super::Command for Taki<'a> {
                 msg.channel_id.send_message(&ctx.http, |m| {
                     m.embed(|e| {
                         e.color(crate::EMBED_COLOR);
-                        e.title("Мастера Таки");
+                        e.title(match view {
+                            StatsView::Current => format!("Мастера Таки, сезон {}", current_season),
+                            StatsView::Season(s) => format!("Мастера Таки, сезон {}", s),
+                            StatsView::AllTime => "Мастера Таки за все время".to_owned(),
+                        });
                         e.description(stats);
                         e
                     });
@@ -174,10 +578,11 @@ impl<'a> super::Command for Taki<'a> {
 
                 Ok(true)
             }
-            ("!takisuspects", _) => {
+            (("!takisuspects", _), _) => {
                 let suspects = self
                     .suspect_picker
                     .list_suspects()
+                    .iter()
                     .enumerate()
                     .map(|(idx, author)| {
                         format!(
@@ -201,109 +606,152 @@ impl<'a> super::Command for Taki<'a> {
 
                 Ok(true)
             }
-            (_, Some(ref mut game)) => {
-                let text = msg.content.to_lowercase();
-                let suspect_name = &game.suspect.short_name;
+            (("!guess", args), Some(_)) if !args.is_empty() => self.guess(ctx, msg, Some(args)),
+            _ => Ok(false),
+        }
+    }
 
-                let uid: u64 = msg.author.id.0;
-                self.storage.incr_in_set(KEY_NUM_TRIES, uid, 1)?;
+    fn handle_unclaimed_message(&mut self, ctx: &Context, msg: &Message) -> JoeResult<bool> {
+        if msg.content.starts_with('!') {
+            return Ok(false);
+        }
+        self.guess(ctx, msg, None)
+    }
 
-                game.answers.push(msg.author.id);
+    fn handle_tick(&mut self, http: &Http, now: NaiveDateTime) -> JoeResult<()> {
+        let expired = match (&self.ongoing, self.timeout) {
+            (Some(game), Some(timeout)) => now - game.started_at >= timeout,
+            _ => false,
+        };
+        if expired {
+            let title = TIMEOUT_MESSAGES.choose(&mut self.rng).unwrap();
+            self.end_lost_game(http, self.channel_id, title)?;
+        }
 
-                if self.suspect_matcher.matches_short_name(&text, suspect_name) {
-                    self.storage.incr_in_set(KEY_NUM_WINS, uid, 1)?;
+        if let Some(length) = self.season_length {
+            if now - season::season_started_at(&mut self.storage, now)? >= length {
+                self.close_season(http, now)?;
+            }
+        }
+        Ok(())
+    }
+}
 
-                    let score: u32 = (MAX_TRIES + 1 - game.answers.len()) as u32;
-                    self.storage.incr_in_set(KEY_SCORE, uid, score as i32)?;
+impl Difficulty {
+    fn parse(level: &str) -> Option<Self> {
+        match level {
+            "easy" => Some(Difficulty::Easy),
+            "" | "normal" => Some(Difficulty::Normal),
+            "hard" => Some(Difficulty::Hard),
+            _ => None,
+        }
+    }
 
-                    for answer_uid in &game.answers {
-                        if answer_uid.0 != uid {
-                            self.storage.rem_from_set(KEY_CURR_STREAK, answer_uid.0)?;
-                        }
-                    }
+    fn texts_shown(self) -> usize {
+        match self {
+            Difficulty::Easy => 4,
+            Difficulty::Normal => 3,
+            Difficulty::Hard => 2,
+        }
+    }
 
-                    // Guessed on the first try?
-                    let curr_streak =
-                        if game.answers.iter().filter(|&&a| a == msg.author.id).count() == 1 {
-                            self.storage.incr_in_set(KEY_CURR_STREAK, uid, 1)?
-                        } else {
-                            self.storage.rem_from_set(KEY_CURR_STREAK, uid)?;
-                            0
-                        };
-
-                    self.storage
-                        .add_gt_to_set(KEY_BEST_STREAK, uid, curr_streak)?;
-                    let best_streak = self.storage.get_in_set(KEY_BEST_STREAK, uid)?;
-
-                    let (title, score_msg) = match curr_streak {
-                        0 | 1 => {
-                            let title = WIN_MESSAGES.choose(&mut self.rng).unwrap();
-                            (title, format!("{} получает +{}", msg.author.name, score))
-                        }
-                        _ => {
-                            let hits_word = if curr_streak % 100 > 4 && curr_streak % 100 < 20 {
-                                "попаданий"
-                            } else {
-                                match curr_streak % 10 {
-                                    1 => "попадание",
-                                    2 | 3 | 4 => "попадания",
-                                    _ => "попаданий",
-                                }
-                            };
-                            let msg = if curr_streak < best_streak {
-                                format!("{} пришпорил коня и понесся вперед! +{} и {} {} подряд в кармане", msg.author.name, score, curr_streak, hits_word)
-                            } else {
-                                format!("{} сегодня определенно в ударе! {} {} подряд, я чуть из седла не выпал, когда услыхал! Забирай свои +{} и мчись вперед", msg.author.name, curr_streak, hits_word, score)
-                            };
-                            let title = WIN_STREAK_MESSAGES.choose(&mut self.rng).unwrap();
-                            (title, msg)
-                        }
-                    };
+    fn weigh_score(self, score: u32) -> u32 {
+        match self {
+            Difficulty::Easy => score.div_ceil(2),
+            Difficulty::Normal => score,
+            Difficulty::Hard => score * 2,
+        }
+    }
+}
 
-                    let resp = format!(
-                        "Это был _{}_ под псевдонимом `{}`\n\n{}",
-                        game.suspect.full_name, game.suspect.short_name, score_msg
-                    );
-                    self.ongoing = None;
-
-                    msg.channel_id.send_message(&ctx.http, |m| {
-                        m.embed(|e| {
-                            e.color(crate::EMBED_COLOR);
-                            e.title(title);
-                            e.description(resp);
-                            e
-                        });
-                        m
-                    })?;
-                } else {
-                    if game.answers.len() == MAX_TRIES {
-                        let title = LOSE_MESSAGES.choose(&mut self.rng).unwrap();
-                        let resp = format!(
-                            "Это был _{}_ под псевдонимом `{}`",
-                            game.suspect.full_name, game.suspect.short_name
-                        );
-
-                        // Your streaks end here, partners. Easy come, easy go...
-                        for uid in &game.answers {
-                            self.storage.rem_from_set(KEY_CURR_STREAK, uid.0)?;
-                        }
+fn update_ratings(
+    storage: &mut storage::ChatGameStorage,
+    game: &OngoingGame,
+    winner: Option<UserId>,
+) -> JoeResult<()> {
+    let suspect_rating = rating::suspect_rating(game.difficulty);
+    let mut players = game.answers.clone();
+    players.sort();
+    players.dedup();
+    for uid in players {
+        let winning_answer_idx = match winner {
+            Some(w) if w == uid => Some(game.answers.len() - 1),
+            _ => None,
+        };
+        let old_rating = match storage.get_in_set(KEY_RATING, uid.0)? {
+            0 => rating::INITIAL_RATING,
+            r => r,
+        };
+        let new_rating = rating::updated_rating(
+            old_rating,
+            suspect_rating,
+            rating::game_outcome(winning_answer_idx),
+        );
+        storage.incr_in_set(KEY_RATING, uid.0, new_rating - old_rating)?;
+        storage.push_to_capped_list(
+            &format!("{}:{}", KEY_RATING_HISTORY, uid.0),
+            &new_rating.to_string(),
+            RATING_HISTORY_LEN,
+        )?;
+    }
+    Ok(())
+}
 
-                        self.ongoing = None;
-
-                        msg.channel_id.send_message(&ctx.http, |m| {
-                            m.embed(|e| {
-                                e.color(crate::EMBED_COLOR);
-                                e.title(title);
-                                e.description(resp);
-                                e
-                            });
-                            m
-                        })?;
-                    }
-                }
-                Ok(true)
-            }
-            _ => Ok(false),
+fn game_hints(
+    suspect: &Author,
+    texts: &[SuspectText],
+    hint_text: Option<SuspectText>,
+) -> Vec<String> {
+    let mut hints = Vec::new();
+    let mut years = texts.iter().map(|t| t.year).collect::<Vec<_>>();
+    if let Some(t) = hint_text {
+        hints.push(format!("А еще подозреваемый говаривал:\n* {}", t.text));
+        years.push(t.year);
+    }
+    match (years.iter().min(), years.iter().max()) {
+        (Some(first), Some(last)) if first == last => {
+            hints.push(format!("Все это было сказано в {} году.", first))
+        }
+        (Some(first), Some(last)) => {
+            hints.push(format!("Все это было сказано с {} по {} год.", first, last))
         }
+        _ => {}
+    }
+    if let Some(letter) = suspect.short_name.chars().next() {
+        hints.push(format!(
+            "Имя подозреваемого начинается на `{}`.",
+            letter.to_uppercase()
+        ));
+    }
+    hints
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_game_hints() {
+        let suspect = Author {
+            short_name: "денко".into(),
+            full_name: "Денис Котов".into(),
+        };
+        let text = |year| SuspectText {
+            text: "текст".into(),
+            year,
+        };
+        let hints = game_hints(&suspect, &[text(2019), text(2018)], Some(text(2020)));
+        assert_eq!(
+            hints,
+            vec![
+                "А еще подозреваемый говаривал:\n* текст",
+                "Все это было сказано с 2018 по 2020 год.",
+                "Имя подозреваемого начинается на `Д`."
+            ]
+        );
+
+        let hints = game_hints(&suspect, &[text(2019)], None);
+        assert_eq!(hints[0], "Все это было сказано в 2019 году.");
+        assert_eq!(hints.len(), 2);
     }
 }
@@ -1,104 +1,216 @@
+use super::Difficulty;
 use crate::{
     config::UserPenalties,
-    messages::{Author, MessageDump},
+    messages::{Author, Message, MessageDump},
 };
+use chrono::Datelike;
 use rand::{rngs::SmallRng, seq::SliceRandom};
+use serde::{Deserialize, Serialize};
+use serenity::prelude::RwLock;
+use std::collections::{HashMap, HashSet};
 
 const MIN_NUM_WORDS: usize = 2;
+const NUM_SIGNATURE_WORDS: usize = 50;
+const EASY_POOL_FACTOR: usize = 5;
+const HARD_MAX_WORDS: usize = 8;
 
 pub struct SuspectPicker<'a> {
-    suspects: Vec<Suspect<'a>>,
+    messages: &'a RwLock<MessageDump>,
     user_penalties: &'a UserPenalties,
-    game_idx: usize,
+    conversation: Option<&'a str>,
+    history: PickHistory,
 }
 
-struct Suspect<'a> {
-    author: &'a Author,
-    texts: Vec<&'a str>,
-    last_pick_game_idx: Option<usize>,
+#[derive(Default, Serialize, Deserialize)]
+pub struct PickHistory {
+    last_pick_game_idxs: HashMap<String, usize>, // short_name -> game_idx
+    game_idx: usize,
 }
 
-impl<'a> SuspectPicker<'a> {
-    pub fn new(messages: &'a MessageDump, user_penalties: &'a UserPenalties) -> Self {
-        let mut suspects = messages
-            .authors
-            .iter()
-            .enumerate()
-            .map(|(idx, author)| {
-                let texts = messages
-                    .texts
-                    .iter()
-                    .filter(|m| {
-                        m.author_idx == idx
-                            && m.text.chars().filter(|&c| c == ' ').count() >= MIN_NUM_WORDS
-                    })
-                    .map(|m| m.text.as_str())
-                    .collect::<Vec<_>>();
-
-                Suspect {
-                    author,
-                    texts,
-                    last_pick_game_idx: None,
-                }
-            })
-            .collect::<Vec<Suspect>>();
+struct Suspect<'m> {
+    author_idx: usize,
+    texts: Vec<&'m Message>,
+}
 
-        // Sort by number of texts descending
-        suspects.sort_by(|a, b| b.texts.len().cmp(&a.texts.len()));
+pub struct SuspectText {
+    pub text: String,
+    pub year: i32,
+}
 
-        user_penalties.verify_penalty_cap(suspects.len());
+impl<'a> SuspectPicker<'a> {
+    pub fn new(
+        messages: &'a RwLock<MessageDump>,
+        user_penalties: &'a UserPenalties,
+        conversation: Option<&'a str>,
+    ) -> Self {
+        let dump = messages.read();
+        user_penalties.verify_penalty_cap(dump.authors.len());
+        if let Some(name) = conversation {
+            if dump.conversation_idx(name).is_none() {
+                panic!(
+                    "Error: Taki is limited to conversation {}, which is not among the message dumps",
+                    name
+                );
+            }
+        }
+        drop(dump);
 
         Self {
-            suspects,
+            messages,
             user_penalties,
-            game_idx: 0,
+            conversation,
+            history: PickHistory::default(),
         }
     }
 
-    pub fn list_suspects(&self) -> impl Iterator<Item = &Author> {
-        self.suspects.iter().map(|s| s.author)
+    pub fn history(&self) -> &PickHistory {
+        &self.history
+    }
+
+    pub fn restore_history(&mut self, history: PickHistory) {
+        self.history = history;
+    }
+
+    pub fn list_suspects(&self) -> Vec<Author> {
+        let messages = self.messages.read();
+        collect_suspects(&messages, self.conversation)
+            .into_iter()
+            .map(|s| messages.authors[s.author_idx].clone())
+            .collect()
     }
 
     pub fn random_suspect(
         &mut self,
         rng: &mut SmallRng,
         num_texts: usize,
-    ) -> (&'a Author, Vec<&'a str>) {
-        let num_suspects = self.suspects.len();
+        difficulty: Difficulty,
+    ) -> Option<(Author, Vec<SuspectText>)> {
+        let messages = self.messages.read();
+        let suspects = collect_suspects(&messages, self.conversation);
+
+        let num_suspects = suspects.len();
         let penalties = self.user_penalties;
-        let last_game_idx = self.game_idx;
+        let last_game_idx = self.history.game_idx;
+        let last_picks = &self.history.last_pick_game_idxs;
 
-        let suspect = self
-            .suspects
-            .choose_weighted_mut(rng, |s| {
-                suspect_weight(s, num_suspects, last_game_idx, penalties)
+        // Nobody left after opt-outs, or the only suspect was picked last game
+        let suspect = suspects
+            .choose_weighted(rng, |s| {
+                let author = &messages.authors[s.author_idx];
+                let last_pick = last_picks.get(&author.short_name).copied();
+                suspect_weight(author, last_pick, num_suspects, last_game_idx, penalties)
             })
-            .unwrap();
+            .ok()?;
 
-        let sample_texts = suspect
-            .texts
+        let signature_stems = match difficulty {
+            Difficulty::Normal => HashSet::new(),
+            _ => messages
+                .signature_words(suspect.author_idx, NUM_SIGNATURE_WORDS)
+                .into_iter()
+                .map(|w| w.stem)
+                .collect::<HashSet<_>>(),
+        };
+        let signature_hits = |m: &Message| {
+            messages
+                .stems(&m.text)
+                .filter(|s| signature_stems.contains(s))
+                .count()
+        };
+        let sample_texts = candidate_texts(&suspect.texts, signature_hits, difficulty, num_texts)
             .choose_multiple(rng, num_texts)
-            .copied()
+            .map(|m| SuspectText {
+                text: m.text.to_owned(),
+                year: m.date.year(),
+            })
             .collect::<Vec<_>>();
 
-        self.game_idx += 1;
-        suspect.last_pick_game_idx = Some(self.game_idx);
+        let author = messages.authors[suspect.author_idx].clone();
+        self.history.game_idx += 1;
+        self.history
+            .last_pick_game_idxs
+            .insert(author.short_name.to_owned(), self.history.game_idx);
 
-        (suspect.author, sample_texts)
+        Some((author, sample_texts))
+    }
+}
+
+fn collect_suspects<'m>(messages: &'m MessageDump, conversation: Option<&str>) -> Vec<Suspect<'m>> {
+    let conversation_idx = conversation.and_then(|c| messages.conversation_idx(c));
+    let mut suspects = (0..messages.authors.len())
+        .map(|author_idx| Suspect {
+            author_idx,
+            texts: Vec::new(),
+        })
+        .collect::<Vec<Suspect>>();
+
+    let texts = messages
+        .texts
+        .iter()
+        .filter(|m| conversation_idx.is_none() || conversation_idx == Some(m.conversation_idx));
+    for m in texts {
+        if m.text.chars().filter(|&c| c == ' ').count() >= MIN_NUM_WORDS {
+            suspects[m.author_idx].texts.push(m);
+        }
+    }
+
+    // Authors who only wrote elsewhere or too briefly have nothing to show in a game
+    suspects.retain(|s| {
+        let author = &messages.authors[s.author_idx];
+        !s.texts.is_empty() && !messages.opted_out().contains(&author.short_name)
+    });
+
+    // Sort by number of texts descending
+    suspects.sort_by_key(|s| std::cmp::Reverse(s.texts.len()));
+    suspects
+}
+
+// Falls back to all texts when there are too few that fit the difficulty
+fn candidate_texts<'m, F>(
+    texts: &[&'m Message],
+    signature_hits: F,
+    difficulty: Difficulty,
+    num_texts: usize,
+) -> Vec<&'m Message>
+where
+    F: Fn(&Message) -> usize,
+{
+    let candidates = match difficulty {
+        Difficulty::Normal => return texts.to_vec(),
+        Difficulty::Easy => {
+            let mut rich = texts
+                .iter()
+                .map(|&m| (signature_hits(m), m))
+                .filter(|&(hits, _)| hits > 0)
+                .collect::<Vec<_>>();
+            rich.sort_by_key(|&(hits, _)| std::cmp::Reverse(hits));
+            rich.truncate(num_texts * EASY_POOL_FACTOR);
+            rich.into_iter().map(|(_, m)| m).collect::<Vec<_>>()
+        }
+        Difficulty::Hard => texts
+            .iter()
+            .copied()
+            .filter(|m| m.text.split_whitespace().count() <= HARD_MAX_WORDS)
+            .filter(|m| signature_hits(m) == 0)
+            .collect(),
+    };
+    if candidates.len() < num_texts {
+        texts.to_vec()
+    } else {
+        candidates
     }
 }
 
 fn suspect_weight(
-    suspect: &Suspect,
+    author: &Author,
+    last_pick_game_idx: Option<usize>,
     num_suspects: usize,
     last_game_idx: usize,
     penalties: &UserPenalties,
 ) -> usize {
-    let name = &suspect.author.short_name;
-    let penalty = penalties.by_short_name(name);
-    let init_weight = num_suspects - penalty;
+    let penalty = penalties.by_short_name(&author.short_name);
+    let init_weight = num_suspects.saturating_sub(penalty);
 
-    match suspect.last_pick_game_idx {
+    match last_pick_game_idx {
         // Not included in the game
         _ if init_weight == 0 => 0,
         // We're just starting the game
@@ -111,3 +223,71 @@ fn suspect_weight(
         Some(last_picked) => init_weight + (last_game_idx - last_picked),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::fixtures::{dump, message};
+
+    #[test]
+    fn test_collect_suspects() {
+        let mut side_message = message(2, "писал только в другой беседе", 2020, 1);
+        side_message.conversation_idx = 1;
+        let mut messages = dump(
+            &["денко", "вова", "саша", "миша"],
+            &["main", "side"],
+            vec![
+                message(0, "первое длинное сообщение тут", 2020, 1),
+                message(0, "второе длинное сообщение тут", 2020, 1),
+                message(1, "одно длинное сообщение тут", 2020, 1),
+                message(3, "коротко", 2020, 1),
+                side_message,
+            ],
+        );
+        fn names(messages: &MessageDump, conversation: Option<&str>) -> Vec<String> {
+            collect_suspects(messages, conversation)
+                .iter()
+                .map(|s| messages.authors[s.author_idx].short_name.to_owned())
+                .collect()
+        }
+
+        assert_eq!(names(&messages, None), vec!["денко", "вова", "саша"]);
+        assert_eq!(names(&messages, Some("main")), vec!["денко", "вова"]);
+        messages.set_opted_out("вова", true);
+        assert_eq!(names(&messages, Some("main")), vec!["денко"]);
+    }
+
+    #[test]
+    fn test_candidate_texts() {
+        let messages = [
+            message(0, "ну это кринж кринж какой-то", 2020, 1),
+            message(0, "обычное сообщение без особых слов", 2020, 1),
+            message(
+                0,
+                "это довольно длинное сообщение, в котором нет ничего особенного вообще",
+                2020,
+                1,
+            ),
+            message(0, "опять кринж", 2020, 1),
+        ];
+        let texts = messages.iter().collect::<Vec<_>>();
+        let hits = |m: &Message| m.text.matches("кринж").count();
+        fn texts_of(picked: Vec<&Message>) -> Vec<&str> {
+            picked.iter().map(|m| m.text.as_str()).collect()
+        }
+
+        assert_eq!(
+            texts_of(candidate_texts(&texts, hits, Difficulty::Easy, 1)),
+            vec!["ну это кринж кринж какой-то", "опять кринж"]
+        );
+        assert_eq!(
+            texts_of(candidate_texts(&texts, hits, Difficulty::Hard, 1)),
+            vec!["обычное сообщение без особых слов"]
+        );
+        assert_eq!(candidate_texts(&texts, hits, Difficulty::Hard, 2).len(), 4);
+        assert_eq!(
+            candidate_texts(&texts, hits, Difficulty::Normal, 3).len(),
+            4
+        );
+    }
+}
@@ -0,0 +1,71 @@
+use super::{Difficulty, MAX_TRIES};
+use crate::charts;
+
+pub const INITIAL_RATING: i32 = 1500;
+const K_FACTOR: f64 = 32.0;
+const DIFFICULTY_SPREAD: i32 = 200;
+const SLOWEST_WIN: f64 = 0.6;
+
+// Every game is a duel between each player who answered and the suspect
+pub fn suspect_rating(difficulty: Difficulty) -> i32 {
+    match difficulty {
+        Difficulty::Easy => INITIAL_RATING - DIFFICULTY_SPREAD,
+        Difficulty::Normal => INITIAL_RATING,
+        Difficulty::Hard => INITIAL_RATING + DIFFICULTY_SPREAD,
+    }
+}
+
+pub fn game_outcome(winning_answer_idx: Option<usize>) -> f64 {
+    match winning_answer_idx {
+        Some(idx) => {
+            let lateness = idx.min(MAX_TRIES - 1) as f64 / (MAX_TRIES - 1) as f64;
+            1.0 - lateness * (1.0 - SLOWEST_WIN)
+        }
+        None => 0.0,
+    }
+}
+
+pub fn updated_rating(rating: i32, opponent_rating: i32, outcome: f64) -> i32 {
+    let expected = 1.0 / (1.0 + 10f64.powf((opponent_rating - rating) as f64 / 400.0));
+    rating + (K_FACTOR * (outcome - expected)).round() as i32
+}
+
+pub fn sparkline(ratings: &[i32]) -> String {
+    let (min, max) = match (ratings.iter().min(), ratings.iter().max()) {
+        (Some(&min), Some(&max)) => (min, max),
+        _ => return String::new(),
+    };
+    charts::sparkline(ratings.iter().map(|&r| match max - min {
+        0 => Some(0.5),
+        range => Some((r - min) as f64 / range as f64),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rating() {
+        assert_eq!(game_outcome(Some(0)), 1.0);
+        assert!((game_outcome(Some(MAX_TRIES - 1)) - SLOWEST_WIN).abs() < 1e-9);
+        assert_eq!(game_outcome(None), 0.0);
+
+        // Evenly matched: half of K either way
+        assert_eq!(updated_rating(1500, 1500, 1.0), 1516);
+        assert_eq!(updated_rating(1500, 1500, 0.0), 1484);
+        // Beating a hard suspect is worth more than beating an easy one
+        let hard = updated_rating(1500, suspect_rating(Difficulty::Hard), 1.0);
+        let easy = updated_rating(1500, suspect_rating(Difficulty::Easy), 1.0);
+        assert!(hard > 1516 && easy < 1516);
+        // Missing an easy one costs more than missing a hard one
+        assert!(
+            updated_rating(1500, suspect_rating(Difficulty::Easy), 0.0)
+                < updated_rating(1500, suspect_rating(Difficulty::Hard), 0.0)
+        );
+
+        assert_eq!(sparkline(&[1500, 1516, 1532, 1508]), "▁▄█▂");
+        assert_eq!(sparkline(&[1500, 1500]), "▄▄");
+        assert_eq!(sparkline(&[]), "");
+    }
+}
@@ -0,0 +1,146 @@
+use super::{KEY_BEST_STREAK, KEY_CURR_STREAK, KEY_NUM_TRIES, KEY_NUM_WINS, KEY_SCORE};
+use crate::{storage::ChatGameStorage, JoeResult};
+use chrono::NaiveDateTime;
+use std::collections::HashMap;
+
+const KEY_SEASON: &str = "season";
+const KEY_SEASON_STARTED: &str = "seasonstarted";
+
+// (set, keep the best result instead of adding seasons up)
+const SEASONAL_SETS: [(&str, bool); 4] = [
+    (KEY_SCORE, false),
+    (KEY_BEST_STREAK, true),
+    (KEY_NUM_TRIES, false),
+    (KEY_NUM_WINS, false),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StatsView {
+    Current,
+    Season(u32),
+    AllTime,
+}
+
+impl StatsView {
+    pub fn parse(arg: &str) -> Option<Self> {
+        match arg {
+            "" => Some(StatsView::Current),
+            "all" => Some(StatsView::AllTime),
+            season => season.parse().ok().map(StatsView::Season),
+        }
+    }
+}
+
+pub fn current_season(storage: &mut ChatGameStorage) -> JoeResult<u32> {
+    match storage.fetch_value(KEY_SEASON)? {
+        Some(season) => Ok(season.parse()?),
+        None => Ok(1),
+    }
+}
+
+pub fn season_started_at(
+    storage: &mut ChatGameStorage,
+    now: NaiveDateTime,
+) -> JoeResult<NaiveDateTime> {
+    match storage.fetch_value(KEY_SEASON_STARTED)? {
+        Some(timestamp) => Ok(NaiveDateTime::from_timestamp(timestamp.parse()?, 0)),
+        None => {
+            storage.store_value(KEY_SEASON_STARTED, &now.timestamp().to_string())?;
+            Ok(now)
+        }
+    }
+}
+
+// Returns the number of the season that ended
+pub fn end_season(storage: &mut ChatGameStorage, now: NaiveDateTime) -> JoeResult<u32> {
+    let season = current_season(storage)?;
+    let archives = SEASONAL_SETS
+        .iter()
+        .map(|&(set, keep_max)| (set, season_key(set, season), all_time_key(set), keep_max))
+        .collect::<Vec<_>>();
+    storage.archive_sets(&archives)?;
+    storage.remove_value(KEY_CURR_STREAK)?;
+    storage.store_value(KEY_SEASON, &(season + 1).to_string())?;
+    storage.store_value(KEY_SEASON_STARTED, &now.timestamp().to_string())?;
+    Ok(season)
+}
+
+pub fn fetch_leaderboard(
+    storage: &mut ChatGameStorage,
+    set: &str,
+    view: StatsView,
+) -> JoeResult<Vec<(u64, i32)>> {
+    match view {
+        StatsView::Current => Ok(storage.fetch_sorted_set(set)?),
+        StatsView::Season(season) => Ok(storage.fetch_sorted_set(&season_key(set, season))?),
+        StatsView::AllTime => {
+            let current = storage.fetch_sorted_set(set)?;
+            let archived = storage.fetch_sorted_set(&all_time_key(set))?;
+            Ok(merge_scores(current, archived, keeps_max(set)))
+        }
+    }
+}
+
+pub fn all_time_score(storage: &mut ChatGameStorage, set: &str, uid: u64) -> JoeResult<i32> {
+    let current = storage.get_in_set(set, uid)?;
+    let archived = storage.get_in_set(&all_time_key(set), uid)?;
+    Ok(if keeps_max(set) {
+        current.max(archived)
+    } else {
+        current + archived
+    })
+}
+
+fn season_key(set: &str, season: u32) -> String {
+    format!("{}:season{}", set, season)
+}
+
+fn all_time_key(set: &str) -> String {
+    format!("{}:alltime", set)
+}
+
+fn keeps_max(set: &str) -> bool {
+    SEASONAL_SETS
+        .iter()
+        .any(|&(seasonal, keep_max)| seasonal == set && keep_max)
+}
+
+fn merge_scores(a: Vec<(u64, i32)>, b: Vec<(u64, i32)>, keep_max: bool) -> Vec<(u64, i32)> {
+    let mut merged: HashMap<u64, i32> = a.into_iter().collect();
+    for (uid, score) in b {
+        match merged.get_mut(&uid) {
+            Some(merged_score) if keep_max => *merged_score = (*merged_score).max(score),
+            Some(merged_score) => *merged_score += score,
+            None => {
+                merged.insert(uid, score);
+            }
+        }
+    }
+    let mut merged = merged.into_iter().collect::<Vec<_>>();
+    merged.sort_by(|(a_uid, a), (b_uid, b)| b.cmp(a).then(a_uid.cmp(b_uid)));
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_scores() {
+        let current = vec![(1, 10), (2, 3)];
+        let archived = vec![(2, 9), (3, 5)];
+        assert_eq!(
+            merge_scores(current.clone(), archived.clone(), false),
+            vec![(2, 12), (1, 10), (3, 5)]
+        );
+        assert_eq!(
+            merge_scores(current, archived, true),
+            vec![(1, 10), (2, 9), (3, 5)]
+        );
+
+        assert_eq!(StatsView::parse(""), Some(StatsView::Current));
+        assert_eq!(StatsView::parse("2"), Some(StatsView::Season(2)));
+        assert_eq!(StatsView::parse("all"), Some(StatsView::AllTime));
+        assert_eq!(StatsView::parse("вчера"), None);
+    }
+}
@@ -1,23 +1,31 @@
-use crate::{messages::MessageDump, JoeResult};
+use super::quote::{QuoteHistory, CONTEXT_REACTION};
+use crate::{config::UserMatcher, messages::MessageDump, JoeResult};
 use rand::{rngs::SmallRng, seq::SliceRandom, SeedableRng};
 use regex::Regex;
 use serenity::{model::prelude::*, prelude::*};
 
 pub struct Wdyt<'a> {
-    messages: &'a MessageDump,
+    messages: &'a RwLock<MessageDump>,
+    user_matcher: &'a UserMatcher,
     trigger_regex: Regex,
     rng: SmallRng,
+    quote_history: QuoteHistory,
 }
 
 impl<'a> Wdyt<'a> {
-    pub fn new(messages: &'a MessageDump) -> JoeResult<Self> {
-        let trigger_regex = Regex::new(r"(?i)(?:что (?:ты )?думаешь (?:об?|про|насчет)|как тебе|(?:тво[её]|ваше) мнение об?|как (?:ты )?относишься ко?)\s+(?P<prompt>.+)").unwrap();
+    pub fn new(
+        messages: &'a RwLock<MessageDump>,
+        user_matcher: &'a UserMatcher,
+    ) -> JoeResult<Self> {
+        let trigger_regex = Regex::new(r"(?i)(?:что (?:ты )?думаешь (?:об?|про|насчет)|что (?P<author>\S+) думает (?:об?|про|насчет)|как тебе|(?:тво[её]|ваше) мнение об?|как (?:ты )?относишься ко?)\s+(?P<prompt>.+)").unwrap();
         let rng = SmallRng::from_entropy();
 
         Ok(Self {
             messages,
+            user_matcher,
             trigger_regex,
             rng,
+            quote_history: QuoteHistory::new(),
         })
     }
 }
@@ -25,19 +33,86 @@ impl<'a> Wdyt<'a> {
 impl<'a> super::Command for Wdyt<'a> {
     fn handle_message(&mut self, ctx: &Context, msg: &Message) -> JoeResult<bool> {
         if let Some(captures) = self.trigger_regex.captures(&msg.content) {
+            let messages = self.messages.read();
             let prompt = &&captures["prompt"];
-            let resp = self
-                .messages
+            let author_idx = match captures.name("author") {
+                Some(name) => match self
+                    .user_matcher
+                    .find_author(&messages.authors, name.as_str())
+                {
+                    Some(idx) => Some(idx),
+                    None => {
+                        msg.channel_id.say(&ctx.http, r"¯\_(ツ)_/¯")?;
+                        return Ok(true);
+                    }
+                },
+                None => None,
+            };
+
+            let by_author = |m: &&crate::messages::Message| {
+                author_idx.is_none() || author_idx == Some(m.author_idx)
+            };
+            let mut picks = messages
                 .containing_all_words(prompt)
-                .choose(&mut self.rng)
-                .map(|msg| msg.text.as_str())
-                .unwrap_or(r"¯\_(ツ)_/¯");
+                .into_iter()
+                .filter(by_author)
+                .collect::<Vec<_>>();
+            if picks.is_empty() {
+                picks = messages
+                    .containing_all_words_or_synonyms(prompt)
+                    .into_iter()
+                    .filter(by_author)
+                    .collect();
+            }
+            if picks.is_empty() {
+                picks = messages
+                    .containing_all_words_fuzzy(prompt)
+                    .into_iter()
+                    .filter(by_author)
+                    .collect();
+            }
 
-            msg.channel_id.say(&ctx.http, resp)?;
+            match (picks.choose(&mut self.rng), author_idx) {
+                (Some(pick), Some(_)) => {
+                    let author = &messages.authors[pick.author_idx];
+                    let posted = msg.channel_id.send_message(&ctx.http, |m| {
+                        m.embed(|e| {
+                            e.color(crate::EMBED_COLOR);
+                            e.description(&pick.text);
+                            e.footer(|f| {
+                                f.text(format!("— {} о {}", author.short_name, prompt));
+                                f
+                            });
+                            e
+                        });
+                        m.reactions(vec![CONTEXT_REACTION]);
+                        m
+                    })?;
+                    self.quote_history
+                        .push(posted.id, messages.index_of(pick).into_iter().collect());
+                }
+                (Some(pick), None) => {
+                    let posted = msg.channel_id.send_message(&ctx.http, |m| {
+                        m.content(&pick.text);
+                        m.reactions(vec![CONTEXT_REACTION]);
+                        m
+                    })?;
+                    self.quote_history
+                        .push(posted.id, messages.index_of(pick).into_iter().collect());
+                }
+                (None, _) => {
+                    msg.channel_id.say(&ctx.http, r"¯\_(ツ)_/¯")?;
+                }
+            }
 
             Ok(true)
         } else {
             Ok(false)
         }
     }
+
+    fn handle_reaction(&mut self, ctx: &Context, rct: &Reaction) -> JoeResult<bool> {
+        self.quote_history
+            .handle_reaction(ctx, rct, &self.messages.read())
+    }
 }
@@ -0,0 +1,142 @@
+use crate::{
+    charts, config::UserMatcher, messages::MessageDump, utils::split_command_rest, JoeResult,
+};
+use chrono::{Datelike, NaiveDate};
+use serenity::{model::prelude::*, prelude::*};
+
+const NUM_WORDS: usize = 80;
+
+pub struct WordCloud<'a> {
+    messages: &'a RwLock<MessageDump>,
+    user_matcher: &'a UserMatcher,
+}
+
+type Period = (NaiveDate, NaiveDate); // both ends inclusive
+
+impl<'a> WordCloud<'a> {
+    pub fn new(messages: &'a RwLock<MessageDump>, user_matcher: &'a UserMatcher) -> Self {
+        Self {
+            messages,
+            user_matcher,
+        }
+    }
+}
+
+impl<'a> super::Command for WordCloud<'a> {
+    fn handle_message(&mut self, ctx: &Context, msg: &Message) -> JoeResult<bool> {
+        let (command, args) = split_command_rest(msg);
+        if command != "!облако" {
+            return Ok(false);
+        }
+
+        let mut name = None;
+        let mut period = None;
+        for arg in args.split_whitespace() {
+            match parse_period(arg) {
+                Some(p) => period = Some(p),
+                None => name = Some(arg),
+            }
+        }
+
+        let messages = self.messages.read();
+        let author_idx = match name {
+            Some(name) => match self.user_matcher.find_author(&messages.authors, name) {
+                Some(idx)
+                    if !messages
+                        .opted_out()
+                        .contains(&messages.authors[idx].short_name) =>
+                {
+                    Some(idx)
+                }
+                _ => {
+                    msg.channel_id
+                        .say(&ctx.http, format!("{}? Не слыхал о таком.", name))?;
+                    return Ok(true);
+                }
+            },
+            None => None,
+        };
+
+        let words = messages.word_frequencies(
+            |m| {
+                !matches!(author_idx, Some(a) if a != m.author_idx)
+                    && !matches!(period, Some((from, to)) if !(from..=to).contains(&m.date.date()))
+            },
+            NUM_WORDS,
+        );
+        if words.is_empty() {
+            msg.channel_id
+                .say(&ctx.http, "Тут и слов-то никаких не найдется, приятель.")?;
+            return Ok(true);
+        }
+
+        let mut title = match author_idx {
+            Some(idx) => format!("Облако слов {}", messages.authors[idx].short_name),
+            None => "Облако слов салуна".to_owned(),
+        };
+        if let Some((from, to)) = period {
+            title.push_str(&format!(
+                ", {} — {}",
+                from.format("%d.%m.%Y"),
+                to.format("%d.%m.%Y")
+            ));
+        }
+        let chart = charts::word_cloud(&words)?;
+        charts::send_chart(ctx, msg.channel_id, chart, |e| {
+            e.title(title);
+            e.footer(|f| f.text("!облако денко 2019 — за год, !облако 09.2019 — за месяц"));
+            e
+        })?;
+
+        Ok(true)
+    }
+}
+
+// 2019 or 09.2019
+fn parse_period(arg: &str) -> Option<Period> {
+    if arg.len() == 4 && arg.chars().all(|c| c.is_ascii_digit()) {
+        let year = arg.parse().ok()?;
+        return Some((
+            NaiveDate::from_ymd_opt(year, 1, 1)?,
+            NaiveDate::from_ymd_opt(year, 12, 31)?,
+        ));
+    }
+    let from = NaiveDate::parse_from_str(&format!("01.{}", arg), "%d.%m.%Y").ok()?;
+    let next_month = match from.month() {
+        12 => NaiveDate::from_ymd_opt(from.year() + 1, 1, 1)?,
+        month => NaiveDate::from_ymd_opt(from.year(), month + 1, 1)?,
+    };
+    Some((from, next_month.pred()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_period() {
+        assert_eq!(
+            parse_period("2019"),
+            Some((
+                NaiveDate::from_ymd(2019, 1, 1),
+                NaiveDate::from_ymd(2019, 12, 31)
+            ))
+        );
+        assert_eq!(
+            parse_period("02.2020"),
+            Some((
+                NaiveDate::from_ymd(2020, 2, 1),
+                NaiveDate::from_ymd(2020, 2, 29)
+            ))
+        );
+        assert_eq!(
+            parse_period("12.2019"),
+            Some((
+                NaiveDate::from_ymd(2019, 12, 1),
+                NaiveDate::from_ymd(2019, 12, 31)
+            ))
+        );
+        assert_eq!(parse_period("денко"), None);
+        assert_eq!(parse_period("13.2019"), None);
+    }
+}
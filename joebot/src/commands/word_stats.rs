@@ -0,0 +1,174 @@
+use crate::{
+    charts::{self, Month},
+    messages::MessageDump,
+    utils::split_command_rest,
+    JoeResult,
+};
+use chrono::Datelike;
+use serenity::{model::prelude::*, prelude::*};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+const TOP_AUTHORS: usize = 10;
+
+pub struct WordStats<'a> {
+    messages: &'a RwLock<MessageDump>,
+}
+
+struct Usage<'m> {
+    per_author: Vec<(usize, usize)>, // (author_idx, count), most frequent first
+    first: &'m crate::messages::Message,
+    per_month: Vec<(Month, usize)>, // including months without uses
+}
+
+impl<'a> WordStats<'a> {
+    pub fn new(messages: &'a RwLock<MessageDump>) -> Self {
+        Self { messages }
+    }
+}
+
+impl<'a> super::Command for WordStats<'a> {
+    fn handle_message(&mut self, ctx: &Context, msg: &Message) -> JoeResult<bool> {
+        let (command, word) = split_command_rest(msg);
+        if command != "!слово" {
+            return Ok(false);
+        }
+        if word.is_empty() {
+            msg.channel_id
+                .say(&ctx.http, "Какое слово, приятель? `!слово кринж`")?;
+            return Ok(true);
+        }
+
+        let messages = self.messages.read();
+        let occurrences = messages
+            .occurrences(&word)
+            .into_iter()
+            .map(|idx| &messages.texts[idx])
+            .collect::<Vec<_>>();
+        let usage = match word_usage(&occurrences) {
+            Some(usage) => usage,
+            None => {
+                msg.channel_id.say(
+                    &ctx.http,
+                    format!("«{}»? В наших краях так не говорят.", word),
+                )?;
+                return Ok(true);
+            }
+        };
+
+        let mut authors = String::new();
+        for &(author_idx, count) in usage.per_author.iter().take(TOP_AUTHORS) {
+            writeln!(
+                &mut authors,
+                "{} — {}",
+                messages.authors[author_idx].short_name, count
+            )?;
+        }
+        let first = format!(
+            "{}, {}:\n{}",
+            usage.first.date.format("%d.%m.%Y"),
+            messages.authors[usage.first.author_idx].short_name,
+            usage.first.text.chars().take(200).collect::<String>()
+        );
+        let ((first_year, first_month), _) = usage.per_month[0];
+        let ((last_year, last_month), _) = usage.per_month[usage.per_month.len() - 1];
+        let ((peak_year, peak_month), peak_count) = usage
+            .per_month
+            .iter()
+            .copied()
+            .max_by_key(|&(_, count)| count)
+            .unwrap();
+        let months = format!(
+            "`{}`\n{:02}.{} — {:02}.{}, чаще всего в {:02}.{} ({})",
+            sparkline(&usage.per_month),
+            first_month,
+            first_year,
+            last_month,
+            last_year,
+            peak_month,
+            peak_year,
+            peak_count
+        );
+
+        let chart = charts::timeline(&format!("«{}» по месяцам", word), &usage.per_month)?;
+        charts::send_chart(ctx, msg.channel_id, chart, |e| {
+            e.title(format!("«{}» — {} сообщений", word, occurrences.len()));
+            e.field("кто говорит", authors, true);
+            e.field("впервые", first, true);
+            e.field("по месяцам", months, false);
+            e
+        })?;
+
+        Ok(true)
+    }
+}
+
+fn word_usage<'m>(occurrences: &[&'m crate::messages::Message]) -> Option<Usage<'m>> {
+    let first = *occurrences.iter().min_by_key(|m| m.date)?;
+
+    let mut per_author = HashMap::new();
+    let mut per_month = HashMap::new();
+    for m in occurrences {
+        *per_author.entry(m.author_idx).or_insert(0) += 1;
+        *per_month
+            .entry((m.date.year(), m.date.month()))
+            .or_insert(0) += 1;
+    }
+
+    let mut per_author = per_author.into_iter().collect::<Vec<_>>();
+    per_author
+        .sort_by(|(a_idx, a_count), (b_idx, b_count)| b_count.cmp(a_count).then(a_idx.cmp(b_idx)));
+
+    let first_month = per_month.keys().min().copied()?;
+    let last_month = per_month.keys().max().copied()?;
+    let months = charts::months_between(first_month, last_month)
+        .into_iter()
+        .map(|m| (m, per_month.get(&m).copied().unwrap_or(0)))
+        .collect();
+
+    Some(Usage {
+        per_author,
+        first,
+        per_month: months,
+    })
+}
+
+fn sparkline(per_month: &[(Month, usize)]) -> String {
+    let max = per_month.iter().map(|&(_, c)| c).max().unwrap_or(0);
+    charts::sparkline(per_month.iter().map(|&(_, count)| match count {
+        0 => None,
+        _ => Some(count as f64 / max as f64),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::fixtures::message;
+
+    #[test]
+    fn test_word_usage() {
+        let messages = [
+            message(1, "", 2019, 11),
+            message(0, "", 2020, 2),
+            message(1, "", 2020, 2),
+            message(1, "", 2020, 2),
+        ];
+        let occurrences = messages.iter().collect::<Vec<_>>();
+        let usage = word_usage(&occurrences).unwrap();
+        assert_eq!(usage.per_author, vec![(1, 3), (0, 1)]);
+        assert!(std::ptr::eq(usage.first, &messages[0]));
+        assert_eq!(
+            usage.per_month,
+            vec![
+                ((2019, 11), 1),
+                ((2019, 12), 0),
+                ((2020, 1), 0),
+                ((2020, 2), 3)
+            ]
+        );
+        assert_eq!(sparkline(&usage.per_month), "▃  █");
+
+        assert!(word_usage(&[]).is_none());
+    }
+}
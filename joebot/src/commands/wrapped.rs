@@ -0,0 +1,283 @@
+use super::taki::PlayerRecord;
+use crate::{
+    charts::{self, Month},
+    config::{Config, UserMatcher},
+    links::DiscordLinks,
+    messages::MessageDump,
+    storage,
+    utils::split_command_rest,
+    JoeResult,
+};
+use chrono::{Datelike, Local, Timelike};
+use serenity::{model::prelude::*, prelude::*};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+const NUM_TOP_WORDS: usize = 10;
+const NUM_TOP_EMOJI: usize = 5;
+const LONGEST_TEXT_LIMIT: usize = 300;
+const MONTHS_IN: [&str; 12] = [
+    "январе",
+    "феврале",
+    "марте",
+    "апреле",
+    "мае",
+    "июне",
+    "июле",
+    "августе",
+    "сентябре",
+    "октябре",
+    "ноябре",
+    "декабре",
+];
+
+pub struct Wrapped<'a> {
+    messages: &'a RwLock<MessageDump>,
+    user_matcher: &'a UserMatcher,
+    discord_links: &'a RwLock<DiscordLinks>,
+    taki_storage: storage::ChatGameStorage,
+}
+
+struct Summary {
+    num_messages: usize,
+    per_month: Vec<(Month, usize)>,
+    busiest_hour: (u32, usize),
+    top_emoji: Vec<(char, usize)>,
+    longest_idx: usize,
+}
+
+impl<'a> Wrapped<'a> {
+    pub fn new(
+        messages: &'a RwLock<MessageDump>,
+        discord_links: &'a RwLock<DiscordLinks>,
+        conf: &'a Config,
+        channel_id: u64,
+        redis: &storage::Redis,
+    ) -> Self {
+        Self {
+            messages,
+            user_matcher: &conf.user_matcher,
+            discord_links,
+            taki_storage: redis.get_game_storage("taki", channel_id),
+        }
+    }
+}
+
+impl<'a> super::Command for Wrapped<'a> {
+    fn handle_message(&mut self, ctx: &Context, msg: &Message) -> JoeResult<bool> {
+        let (command, args) = split_command_rest(msg);
+        if command != "!итоги" {
+            return Ok(false);
+        }
+        let mut name = None;
+        let mut year = Local::now().year();
+        for arg in args.split_whitespace() {
+            match arg.parse::<i32>() {
+                Ok(y) => year = y,
+                Err(_) => name = Some(arg),
+            }
+        }
+
+        let messages = self.messages.read();
+        let linked_idx = self
+            .discord_links
+            .read()
+            .author_idx(&messages.authors, msg.author.id.0);
+        let author_idx = match (name, linked_idx) {
+            (Some(name), _) => self.user_matcher.find_author(&messages.authors, name),
+            (None, Some(idx)) => Some(idx),
+            (None, None) => {
+                msg.channel_id.say(
+                    &ctx.http,
+                    "Чьи итоги подводим? `!итоги денко 2019`, а свои — после `!link имя`",
+                )?;
+                return Ok(true);
+            }
+        };
+        let author_idx = match author_idx {
+            Some(idx)
+                if !messages
+                    .opted_out()
+                    .contains(&messages.authors[idx].short_name) =>
+            {
+                idx
+            }
+            _ => {
+                let reply = match name {
+                    Some(name) => format!("{}? Не слыхал о таком.", name),
+                    None => "Ты ушел в тень, приятель, тут и подводить нечего.".to_owned(),
+                };
+                msg.channel_id.say(&ctx.http, reply)?;
+                return Ok(true);
+            }
+        };
+        let short_name = &messages.authors[author_idx].short_name;
+        let summary = match summarize(&messages, author_idx, year) {
+            Some(summary) => summary,
+            None => {
+                msg.channel_id.say(
+                    &ctx.http,
+                    format!("В {} году от {} в салуне ни слова.", year, short_name),
+                )?;
+                return Ok(true);
+            }
+        };
+
+        let top_words = messages
+            .word_frequencies(
+                |m| m.author_idx == author_idx && m.date.year() == year,
+                NUM_TOP_WORDS,
+            )
+            .into_iter()
+            .map(|(word, count)| format!("{} ({})", word, count))
+            .collect::<Vec<_>>();
+        let top_emoji = summary
+            .top_emoji
+            .iter()
+            .map(|(emoji, count)| format!("{} ({})", emoji, count))
+            .collect::<Vec<_>>();
+        let ((_, peak_month), peak_count) = summary
+            .per_month
+            .iter()
+            .copied()
+            .max_by_key(|&(month, count)| (count, std::cmp::Reverse(month)))
+            .unwrap();
+        let (peak_hour, peak_hour_count) = summary.busiest_hour;
+
+        let longest = messages.full_message(summary.longest_idx);
+        let mut longest_text = longest
+            .text
+            .chars()
+            .take(LONGEST_TEXT_LIMIT)
+            .collect::<String>();
+        if longest_text.len() < longest.text.len() {
+            longest_text.push('…');
+        }
+        let longest_field = format!(
+            "{}, {} символов:\n{}",
+            longest.date.format("%d.%m.%Y"),
+            longest.text.chars().count(),
+            longest_text
+        );
+
+        let mut activity = String::new();
+        writeln!(&mut activity, "{} сообщений", summary.num_messages)?;
+        writeln!(
+            &mut activity,
+            "чаще всего в {} ({})",
+            MONTHS_IN[peak_month as usize - 1],
+            peak_count
+        )?;
+        writeln!(
+            &mut activity,
+            "любимое время — с {} до {} ({})",
+            peak_hour,
+            (peak_hour + 1) % 24,
+            peak_hour_count
+        )?;
+
+        let linked_user = self.discord_links.read().user_id(short_name);
+        let taki = match linked_user {
+            Some(uid) => {
+                let record = PlayerRecord::fetch(&mut self.taki_storage, uid)?;
+                Some(format!(
+                    "{} очков, лучшая серия — {}, попаданий — {} из {}",
+                    record.score, record.best_streak, record.num_wins, record.num_tries
+                ))
+            }
+            None => None,
+        };
+
+        let chart = charts::timeline(&format!("{}, {} год", short_name, year), &summary.per_month)?;
+        charts::send_chart(ctx, msg.channel_id, chart, |e| {
+            e.title(format!("Итоги {} года: {}", year, short_name));
+            e.field("в салуне", activity, false);
+            if !top_words.is_empty() {
+                e.field("любимые слова", top_words.join(", "), false);
+            }
+            if !top_emoji.is_empty() {
+                e.field("любимые эмодзи", top_emoji.join(" "), false);
+            }
+            e.field("самое длинное сообщение", longest_field, false);
+            if let Some(taki) = taki {
+                e.field("таки, за все время", taki, false);
+            }
+            e
+        })?;
+
+        Ok(true)
+    }
+}
+
+fn summarize(messages: &MessageDump, author_idx: usize, year: i32) -> Option<Summary> {
+    let mut num_messages = 0;
+    let mut per_month = [0; 12];
+    let mut per_hour = [0; 24];
+    let mut emoji: HashMap<char, usize> = HashMap::new();
+    let mut longest: Option<(usize, usize)> = None; // (length, idx)
+
+    for (idx, m) in messages.texts.iter().enumerate() {
+        if m.author_idx != author_idx || m.date.year() != year || !messages.is_visible(m) {
+            continue;
+        }
+        for e in emoji_in(&m.text) {
+            *emoji.entry(e).or_insert(0) += 1;
+        }
+        if m.continuation {
+            continue;
+        }
+        num_messages += 1;
+        per_month[m.date.month0() as usize] += 1;
+        per_hour[m.date.hour() as usize] += 1;
+
+        let len = messages.full_message(idx).text.chars().count();
+        if !matches!(longest, Some((longest_len, _)) if longest_len >= len) {
+            longest = Some((len, idx));
+        }
+    }
+
+    let (_, longest_idx) = longest?;
+    let busiest_hour = (0..24)
+        .map(|hour| (hour, per_hour[hour as usize]))
+        .max_by_key(|&(hour, count)| (count, std::cmp::Reverse(hour)))?;
+    let mut top_emoji = emoji.into_iter().collect::<Vec<_>>();
+    top_emoji.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+    top_emoji.truncate(NUM_TOP_EMOJI);
+
+    Some(Summary {
+        num_messages,
+        per_month: (1..=12)
+            .map(|m| ((year, m), per_month[m as usize - 1]))
+            .collect(),
+        busiest_hour,
+        top_emoji,
+        longest_idx,
+    })
+}
+
+// Pictographic emoji, without modifiers and joiners
+fn emoji_in(text: &str) -> impl Iterator<Item = char> + '_ {
+    text.chars().filter(|&c| {
+        matches!(c as u32,
+            0x1f300..=0x1f5ff // symbols and pictographs
+            | 0x1f600..=0x1f64f // emoticons
+            | 0x1f680..=0x1f6ff // transport and map symbols
+            | 0x1f900..=0x1faff // supplemental symbols and pictographs
+            | 0x2600..=0x27bf // miscellaneous symbols and dingbats
+        ) && !matches!(c as u32, 0x1f3fb..=0x1f3ff) // skin tones
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_emoji_in() {
+        assert_eq!(
+            emoji_in("ну 😂😂 и 👍🏻, ❤️ вот").collect::<String>(),
+            "😂😂👍❤"
+        );
+        assert_eq!(emoji_in("без смайлов :) 123").count(), 0);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::messages::{Author, DumpSource, Language};
 use regex::Regex;
 use serde::{
     de::{Error, MapAccess, Visitor},
@@ -11,8 +12,120 @@ use std::{
 #[derive(Deserialize)]
 pub struct Config {
     pub channel_id: u64,
+    #[serde(default)]
+    pub extra_channel_ids: Vec<u64>,
     pub user_matcher: UserMatcher,
     pub user_penalties: UserPenalties,
+    #[serde(default = "default_message_dumps")]
+    pub message_dumps: Vec<MessageDumpSource>,
+    #[serde(default)]
+    pub taki_conversation: Option<String>,
+    #[serde(default = "default_taki_timeout_mins")]
+    pub taki_timeout_mins: u64,
+    #[serde(default)]
+    pub taki_season_days: u64,
+    #[serde(default)]
+    pub admin_ids: Vec<u64>,
+    #[serde(default)]
+    pub indexing: Indexing,
+    #[serde(default)]
+    pub discord_links: HashMap<u64, String>,
+    #[serde(default)]
+    pub ingest_messages: bool,
+    #[serde(default)]
+    pub daily_memory_at: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct MessageDumpSource {
+    pub name: String,
+    pub path: String,
+    #[serde(default)]
+    pub short_names: HashMap<String, String>,
+}
+
+impl MessageDumpSource {
+    pub fn as_dump_source(&self) -> DumpSource<'_> {
+        DumpSource {
+            name: &self.name,
+            path: &self.path,
+            short_names: &self.short_names,
+        }
+    }
+}
+
+fn default_message_dumps() -> Vec<MessageDumpSource> {
+    vec![MessageDumpSource {
+        name: "main".into(),
+        path: "messages.html".into(),
+        short_names: HashMap::new(),
+    }]
+}
+
+impl Config {
+    pub fn channel_ids(&self) -> Vec<u64> {
+        std::iter::once(self.channel_id)
+            .chain(self.extra_channel_ids.iter().copied())
+            .collect()
+    }
+}
+
+fn default_taki_timeout_mins() -> u64 {
+    30
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Indexing {
+    pub languages: Vec<Language>,
+    pub stopwords_path: String,
+    pub synonyms_path: String,
+}
+
+impl Default for Indexing {
+    fn default() -> Self {
+        Self {
+            languages: vec![Language::Russian, Language::English],
+            stopwords_path: "stopwords.json".into(),
+            synonyms_path: "synonyms.json".into(),
+        }
+    }
+}
+
+impl Indexing {
+    pub fn load_stopwords(&self) -> HashSet<String> {
+        let stopwords_str = match std::fs::read_to_string(&self.stopwords_path) {
+            Ok(s) => s,
+            Err(e) => {
+                println!(
+                    "Warning: cannot read {} ({}), no words will be left out of the index",
+                    self.stopwords_path, e
+                );
+                return HashSet::new();
+            }
+        };
+        let stopwords: HashMap<Language, Vec<String>> =
+            serde_json::from_str(&stopwords_str).unwrap();
+        stopwords
+            .into_iter()
+            .filter(|(l, _)| self.languages.contains(l))
+            .flat_map(|(_, words)| words)
+            .collect()
+    }
+
+    pub fn load_synonyms(&self) -> Vec<Vec<String>> {
+        let synonyms_str = match std::fs::read_to_string(&self.synonyms_path) {
+            Ok(s) => s,
+            Err(e) => {
+                println!(
+                    "Warning: cannot read {} ({}), WDYT will not look for synonyms",
+                    self.synonyms_path, e
+                );
+                return Vec::new();
+            }
+        };
+        serde_json::from_str(&synonyms_str).unwrap()
+    }
 }
 
 #[derive(Deserialize)]
@@ -58,6 +171,17 @@ impl UserMatcher {
     pub fn matches_short_name(&self, input: &str, short_name: &str) -> bool {
         self.0[short_name].is_match(input)
     }
+
+    pub fn matches_any(&self, input: &str) -> bool {
+        self.0.values().any(|r| r.is_match(input))
+    }
+
+    pub fn find_author(&self, authors: &[Author], name: &str) -> Option<usize> {
+        let name = name.to_lowercase();
+        authors
+            .iter()
+            .position(|a| self.matches_short_name(&name, &a.short_name))
+    }
 }
 
 impl<'de> Deserialize<'de> for UserMatcher {
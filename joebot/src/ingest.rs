@@ -0,0 +1,96 @@
+use crate::{
+    config::Config,
+    links::DiscordLinks,
+    messages::{redact, MessageDump},
+    storage, JoeResult,
+};
+use chrono::{Local, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use serenity::{model::prelude::*, prelude::*};
+use std::collections::HashSet;
+
+const KEY_MESSAGES: &str = "messages";
+const CONVERSATION: &str = "discord";
+
+#[derive(Serialize, Deserialize)]
+struct IngestedMessage {
+    short_name: String,
+    full_name: String,
+    text: String,
+    timestamp: i64,
+}
+
+pub struct Ingest<'a> {
+    messages: &'a RwLock<MessageDump>,
+    discord_links: &'a RwLock<DiscordLinks>,
+    short_names: HashSet<&'a str>,
+    channel_id: ChannelId,
+    storage: storage::ChatGameStorage,
+}
+
+impl<'a> Ingest<'a> {
+    pub fn new(
+        messages: &'a RwLock<MessageDump>,
+        discord_links: &'a RwLock<DiscordLinks>,
+        conf: &'a Config,
+        redis: &storage::Redis,
+    ) -> Self {
+        Self {
+            messages,
+            discord_links,
+            short_names: conf.user_matcher.short_names(),
+            channel_id: ChannelId(conf.channel_id),
+            storage: redis.get_game_storage("ingest", conf.channel_id),
+        }
+    }
+
+    pub fn restore(&mut self) -> JoeResult<usize> {
+        let stored = self.storage.fetch_list(KEY_MESSAGES)?;
+        let mut messages = self.messages.write();
+        let mut restored = 0;
+        for entry in stored {
+            let m: IngestedMessage = serde_json::from_str(&entry)?;
+            if !self.short_names.contains(m.short_name.as_str()) {
+                continue;
+            }
+            let date = NaiveDateTime::from_timestamp(m.timestamp, 0);
+            if messages.append(CONVERSATION, &m.short_name, &m.full_name, &m.text, date) {
+                restored += 1;
+            }
+        }
+        Ok(restored)
+    }
+
+    pub fn ingest(&mut self, msg: &Message) -> JoeResult<()> {
+        // The corpus is shared by every channel, so only the main one gets to add to it
+        if msg.channel_id != self.channel_id {
+            return Ok(());
+        }
+        let links = self.discord_links.read();
+        let short_name = match links.short_name(msg.author.id.0) {
+            Some(name) if self.short_names.contains(name) => name,
+            _ => return Ok(()),
+        };
+        let date = msg.timestamp.with_timezone(&Local).naive_local();
+        // Contact details shouldn't end up in Redis either
+        let text = redact(&msg.content);
+
+        let mut messages = self.messages.write();
+        if messages.opted_out().contains(short_name) {
+            return Ok(());
+        }
+        let appended = messages.append(CONVERSATION, short_name, &msg.author.name, &text, date);
+        drop(messages);
+        if appended {
+            let entry = IngestedMessage {
+                short_name: short_name.to_owned(),
+                full_name: msg.author.name.to_owned(),
+                text: text.into_owned(),
+                timestamp: date.timestamp(),
+            };
+            self.storage
+                .push_to_list(KEY_MESSAGES, &serde_json::to_string(&entry)?)?;
+        }
+        Ok(())
+    }
+}
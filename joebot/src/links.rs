@@ -0,0 +1,71 @@
+use crate::{config::Config, messages::Author, storage, JoeResult};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const KEY_LINKS: &str = "links";
+
+#[derive(Serialize, Deserialize)]
+struct LinkEntry {
+    user_id: u64,
+    short_name: Option<String>, // None when the user has unlinked themselves
+}
+
+// Links come from the config and from !link, the latter kept in Redis as a log of changes
+pub struct DiscordLinks {
+    by_user_id: HashMap<u64, String>,
+    storage: storage::ChatGameStorage,
+}
+
+impl DiscordLinks {
+    pub fn restore(conf: &Config, redis: &storage::Redis) -> JoeResult<Self> {
+        let mut links = Self {
+            by_user_id: conf.discord_links.clone(),
+            storage: redis.get_game_storage("links", conf.channel_id),
+        };
+        for entry in links.storage.fetch_list(KEY_LINKS)? {
+            let entry: LinkEntry = serde_json::from_str(&entry)?;
+            match entry.short_name {
+                Some(short_name) => links.by_user_id.insert(entry.user_id, short_name),
+                None => links.by_user_id.remove(&entry.user_id),
+            };
+        }
+        Ok(links)
+    }
+
+    pub fn short_name(&self, user_id: u64) -> Option<&str> {
+        self.by_user_id.get(&user_id).map(|s| s.as_str())
+    }
+
+    pub fn user_id(&self, short_name: &str) -> Option<u64> {
+        self.by_user_id
+            .iter()
+            .find(|(_, name)| name.as_str() == short_name)
+            .map(|(&user_id, _)| user_id)
+    }
+
+    pub fn author_idx(&self, authors: &[Author], user_id: u64) -> Option<usize> {
+        let short_name = self.short_name(user_id)?;
+        authors.iter().position(|a| a.short_name == short_name)
+    }
+
+    pub fn link(&mut self, user_id: u64, short_name: &str) -> JoeResult<()> {
+        self.persist(user_id, Some(short_name))?;
+        self.by_user_id.insert(user_id, short_name.to_owned());
+        Ok(())
+    }
+
+    pub fn unlink(&mut self, user_id: u64) -> JoeResult<Option<String>> {
+        self.persist(user_id, None)?;
+        Ok(self.by_user_id.remove(&user_id))
+    }
+
+    fn persist(&mut self, user_id: u64, short_name: Option<&str>) -> JoeResult<()> {
+        let entry = LinkEntry {
+            user_id,
+            short_name: short_name.map(|s| s.to_owned()),
+        };
+        self.storage
+            .push_to_list(KEY_LINKS, &serde_json::to_string(&entry)?)?;
+        Ok(())
+    }
+}
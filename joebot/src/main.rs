@@ -1,17 +1,23 @@
 use lazy_static::lazy_static;
 use serenity::{builder::CreateMessage, model::prelude::*, prelude::*, utils::Color};
 use std::cell::RefCell;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::fs::File;
+use std::os::unix::net::UnixStream;
+use std::sync::Arc;
 
 pub type JoeResult<T> = Result<T, Box<dyn Error>>;
 
 pub const EMBED_COLOR: Color = Color::new(0x7a4c50);
 
+mod charts;
 mod commands;
 mod config;
+mod ingest;
+mod links;
 mod messages;
+mod scheduler;
 mod storage;
 mod utils;
 
@@ -20,9 +26,19 @@ lazy_static! {
         let conf_str = std::fs::read_to_string("config.json").expect("Cannot read config.json");
         serde_json::from_str(&conf_str).unwrap()
     };
-    static ref MESSAGE_DUMP: messages::MessageDump = {
+    static ref MESSAGE_DUMP: RwLock<messages::MessageDump> = {
         let msg_names: HashSet<&str> = CONFIG.user_matcher.short_names();
-        let messages = messages::MessageDump::from_file("messages.html", &msg_names);
+        let stemmer = messages::Stemmer::new(
+            &CONFIG.indexing.languages,
+            CONFIG.indexing.load_stopwords(),
+            CONFIG.indexing.load_synonyms(),
+        );
+        let sources = CONFIG
+            .message_dumps
+            .iter()
+            .map(|d| d.as_dump_source())
+            .collect::<Vec<_>>();
+        let messages = messages::MessageDump::from_files(&sources, &msg_names, stemmer);
         let message_authors = messages
             .authors
             .iter()
@@ -30,18 +46,20 @@ lazy_static! {
             .collect::<Vec<_>>()
             .join(", ");
         println!(
-            "{} messages from the following authors: {}\n",
+            "{} messages from {} by the following authors: {}\n",
             messages.texts.len(),
+            messages.conversations.join(", "),
             message_authors
         );
-        messages
+        RwLock::new(messages)
     };
 }
 
 struct Handler<'a> {
     bot_user: Mutex<RefCell<Option<CurrentUser>>>,
-    bot_channel_id: ChannelId,
-    dispatcher: Mutex<commands::CommandDispatcher<'a>>,
+    bot_channel_ids: HashSet<ChannelId>,
+    dispatcher: Arc<Mutex<commands::CommandDispatcher<'a>>>,
+    ingest: Option<Mutex<ingest::Ingest<'a>>>,
 }
 
 impl<'a> Handler<'a> {
@@ -58,6 +76,13 @@ impl<'a> Handler<'a> {
             msg.channel_id
                 .send_message(&ctx.http, bot_help)
                 .map_err(|e| format!("Help: {:?}", e))?;
+            return Ok(());
+        }
+        if let Some(ref ingest) = self.ingest {
+            ingest
+                .lock()
+                .ingest(&msg)
+                .map_err(|e| format!("Ingest: {:?}", e))?;
         }
         Ok(())
     }
@@ -80,7 +105,7 @@ impl<'a> EventHandler for Handler<'a> {
     }
 
     fn message(&self, ctx: Context, msg: Message) {
-        if msg.channel_id != self.bot_channel_id {
+        if !self.bot_channel_ids.contains(&msg.channel_id) {
             return;
         }
         if let Err(e) = self.handle_message(ctx, msg) {
@@ -89,7 +114,7 @@ impl<'a> EventHandler for Handler<'a> {
     }
 
     fn reaction_add(&self, ctx: Context, rct: Reaction) {
-        if rct.channel_id != self.bot_channel_id {
+        if !self.bot_channel_ids.contains(&rct.channel_id) {
             return;
         }
         if let Err(e) = self.handle_reaction(ctx, rct) {
@@ -106,16 +131,37 @@ fn main() {
         .map_err(|e| format!("redis: {}", e))
         .unwrap();
 
+    let discord_links = links::DiscordLinks::restore(&CONFIG, &redis)
+        .map_err(|e| format!("links: {}", e))
+        .unwrap();
+    // Shared by the handlers for the lifetime of the process, like the statics above
+    let discord_links: &'static RwLock<links::DiscordLinks> =
+        Box::leak(Box::new(RwLock::new(discord_links)));
+
+    let ingest = if CONFIG.ingest_messages {
+        let mut ingest = ingest::Ingest::new(&MESSAGE_DUMP, discord_links, &CONFIG, &redis);
+        let restored = ingest
+            .restore()
+            .map_err(|e| format!("ingest: {}", e))
+            .unwrap();
+        println!("* Restored {} messages ingested from Discord", restored);
+        Some(Mutex::new(ingest))
+    } else {
+        None
+    };
+
     println!("* Starting command handlers");
-    let dispatcher = Mutex::new(init_dispatcher(&CONFIG, &redis));
+    let dispatcher = Arc::new(Mutex::new(init_dispatcher(&CONFIG, discord_links, &redis)));
     let handler = Handler {
         bot_user: Mutex::new(RefCell::new(None)),
-        bot_channel_id: ChannelId(CONFIG.channel_id),
-        dispatcher,
+        bot_channel_ids: CONFIG.channel_ids().into_iter().map(ChannelId).collect(),
+        dispatcher: dispatcher.clone(),
+        ingest,
     };
 
     println!("* Connecting to Discord");
     let mut client = Client::new(&bot_token, handler).unwrap();
+    scheduler::start(dispatcher, client.cache_and_http.http.clone());
 
     if let Err(e) = client.start() {
         eprintln!("Client error: {:?}", e);
@@ -124,26 +170,84 @@ fn main() {
 
 fn init_dispatcher<'a>(
     conf: &'a config::Config,
+    discord_links: &'a RwLock<links::DiscordLinks>,
     redis: &storage::Redis,
 ) -> commands::CommandDispatcher<'a> {
     let chain_data: joebot_markov_chain::MarkovChain =
         bincode::deserialize_from(File::open("chain.bin").unwrap()).unwrap();
+    // Shared by every channel's commands, the chain is too big to load more than once
+    let chain_data: &'static joebot_markov_chain::MarkovChain = Box::leak(Box::new(chain_data));
+    // The generator and the classifier serve a single connection at a time
+    let random_text_generator: &'static Mutex<UnixStream> = Box::leak(Box::new(Mutex::new(
+        UnixStream::connect("randtext.sock").unwrap(),
+    )));
+    let image_classifier: &'static Mutex<UnixStream> = Box::leak(Box::new(Mutex::new(
+        UnixStream::connect("imclassif.sock").unwrap(),
+    )));
 
-    let taki = commands::Taki::new(&MESSAGE_DUMP, &conf, redis);
-    let chain = commands::Chain::new(chain_data);
+    // Linked users and opt-outs are the same in every channel
+    let linking = commands::Linking::new(&MESSAGE_DUMP, discord_links, conf);
+    let privacy = commands::Privacy::new(&MESSAGE_DUMP, discord_links, conf, redis).unwrap();
+    let shared: commands::Commands<'a> = vec![Box::new(linking), Box::new(privacy)];
+
+    let channels = conf
+        .channel_ids()
+        .into_iter()
+        .map(|channel_id| {
+            let commands = init_channel_commands(
+                conf,
+                channel_id,
+                chain_data,
+                random_text_generator,
+                image_classifier,
+                discord_links,
+                redis,
+            );
+            (ChannelId(channel_id), commands)
+        })
+        .collect::<HashMap<_, _>>();
+    commands::CommandDispatcher::new(shared, channels)
+}
+
+fn init_channel_commands<'a>(
+    conf: &'a config::Config,
+    channel_id: u64,
+    chain_data: &'a joebot_markov_chain::MarkovChain,
+    random_text_generator: &'a Mutex<UnixStream>,
+    image_classifier: &'a Mutex<UnixStream>,
+    discord_links: &'a RwLock<links::DiscordLinks>,
+    redis: &storage::Redis,
+) -> commands::Commands<'a> {
+    let taki = commands::Taki::new(&MESSAGE_DUMP, discord_links, conf, channel_id, redis).unwrap();
+    let chain = commands::Chain::new(chain_data, &MESSAGE_DUMP, &conf.user_matcher);
     let poll = commands::Poll::new();
-    let wdyt = commands::Wdyt::new(&MESSAGE_DUMP).unwrap();
-    let joker = commands::Joker::new(&MESSAGE_DUMP).unwrap();
-    let img2msg = commands::Img2msg::new(&MESSAGE_DUMP).unwrap();
+    let grep = commands::Grep::new(&MESSAGE_DUMP, &conf.user_matcher);
+    let word_stats = commands::WordStats::new(&MESSAGE_DUMP);
+    let signature_words =
+        commands::SignatureWords::new(&MESSAGE_DUMP, &conf.user_matcher, discord_links);
+    let word_cloud = commands::WordCloud::new(&MESSAGE_DUMP, &conf.user_matcher);
+    let wrapped = commands::Wrapped::new(&MESSAGE_DUMP, discord_links, conf, channel_id, redis);
+    let activity = commands::Activity::new(&MESSAGE_DUMP);
+    let memories = commands::Memories::new(&MESSAGE_DUMP, conf, channel_id).unwrap();
+    let wdyt = commands::Wdyt::new(&MESSAGE_DUMP, &conf.user_matcher).unwrap();
+    let joker = commands::Joker::new(&MESSAGE_DUMP, random_text_generator).unwrap();
+    let img2msg = commands::Img2msg::new(&MESSAGE_DUMP, image_classifier).unwrap();
 
-    commands::CommandDispatcher::new(vec![
+    vec![
         Box::new(taki),
         Box::new(chain),
         Box::new(poll),
+        Box::new(grep),
+        Box::new(word_stats),
+        Box::new(signature_words),
+        Box::new(word_cloud),
+        Box::new(wrapped),
+        Box::new(activity),
+        Box::new(memories),
         Box::new(wdyt),
         Box::new(joker),
         Box::new(img2msg),
-    ])
+    ]
 }
 
 fn bot_help<'a, 'b>(m: &'b mut CreateMessage<'a>) -> &'b mut CreateMessage<'a> {
@@ -153,9 +257,10 @@ fn bot_help<'a, 'b>(m: &'b mut CreateMessage<'a>) -> &'b mut CreateMessage<'a> {
         e.field(
             "таки",
             r#"
-`!takistart` — начнем партию
+`!takistart` — начнем партию (`!takistart easy` или `hard` — полегче или посложнее, и очков меньше или больше)
 `!takisuspects` — бросим взгляд на плакаты о розыске
-`!takistats` — поднимем бокал крепкого виски за самых метких стрелков
+`!takistats` — поднимем бокал крепкого виски за самых метких стрелков (`!takistats N` — сезона N, `!takistats all` — всех времен)
+_чтобы ответить, назови подозреваемого по имени, упомяни его или напиши `!guess имя`_
                     "#,
             false,
         );
@@ -171,6 +276,8 @@ fn bot_help<'a, 'b>(m: &'b mut CreateMessage<'a>) -> &'b mut CreateMessage<'a> {
             "политика",
             r#"
 `!poll` — устроим честный суд
+`!link денко` — представимся джо, чтобы он знал, чьи сообщения твои (подтверждает ✅ прежний владелец имени или админ; `!unlink` — забудем)
+`!optout` — уйдем в тень: твои сообщения пропадут из игр (`!optin` — вернемся)
 "#,
             false,
         );
@@ -178,9 +285,25 @@ fn bot_help<'a, 'b>(m: &'b mut CreateMessage<'a>) -> &'b mut CreateMessage<'a> {
             "поговорим с джо",
             r#"
 `что думаешь об итмо и бонче`
+`что денко думает об итмо`
 `джокер++`
 `джокер про итмо`
 `джокер про итмо и бонч`
+
+_📜 под цитатой — вспомним, о чем тогда шла речь_
+"#,
+            false,
+        );
+        e.field(
+            "архивы",
+            r#"
+`!grep итмо|бонч` — поищем в старых переписках (`!grep` — подробнее)
+`!слово кринж` — узнаем, кто и с каких пор так говорит
+`!словарь денко` — словечки, по которым узнают жителя
+`!облако денко 2019` — нарисуем облако слов (имя и год можно опустить)
+`!итоги денко 2019` — подведем итоги года (свои — просто `!итоги`)
+`!активность` — вспомним, кто и когда держал салун на ушах
+`!вэтотдень` — поднимем старые записи, сделанные в этот же день
 "#,
             false,
         );
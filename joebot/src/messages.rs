@@ -1,10 +1,28 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
+use std::ops::Range;
 use vkopt_message_parser::reader::{fold_html, EventResult, MessageEvent};
 
+mod fuzzy;
+mod language;
+mod redact;
+mod vocabulary;
+
+pub use language::Language;
+pub use redact::redact;
+
 const DISCORD_TEXT_LIMIT: usize = 2000;
 
-#[derive(Debug, Clone, PartialEq)]
+pub struct DumpSource<'s> {
+    pub name: &'s str,
+    pub path: &'s str,
+    // Short names used in the dump -> the ones used in the config
+    pub short_names: &'s HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Author {
     pub short_name: String,
     pub full_name: String,
@@ -14,81 +32,229 @@ pub struct Author {
 pub struct Message {
     pub text: String,
     pub author_idx: usize,
+    pub date: NaiveDateTime,
+    pub conversation_idx: usize,
+    // Set for all but the first part of a message that was too long to be kept whole
+    pub continuation: bool,
+}
+
+pub struct FullMessage<'a> {
+    pub author_idx: usize,
+    pub date: NaiveDateTime,
+    pub text: Cow<'a, str>,
+    pub parts: Range<usize>,
 }
 
 #[derive(Debug)]
 pub struct MessageDump {
     pub authors: Vec<Author>,
+    pub conversations: Vec<String>,
     pub texts: Vec<Message>,
     word_stem_to_text_idx: HashMap<String, Vec<u32>>,
+    fuzzy_stems: fuzzy::FuzzyStems,
     stemmer: Stemmer,
+    opted_out: HashSet<String>,
 }
 
 impl MessageDump {
-    pub fn from_file(input_file: &str, names: &HashSet<&str>) -> Self {
+    // Messages that appear in more than one dump are only kept from the first one listed
+    pub fn from_files(sources: &[DumpSource], names: &HashSet<&str>, stemmer: Stemmer) -> Self {
         let mut authors: Vec<Author> = Vec::new();
-        let mut last_full_name: String = String::new();
-
-        let texts = fold_html(
-            input_file,
-            Vec::new(),
-            |mut msgs: Vec<Message>, event| match event {
-                MessageEvent::Start(_) => match msgs.last_mut() {
-                    Some(msg)
-                        if msg.text.trim().is_empty()
-                            || msg.text.chars().count() >= DISCORD_TEXT_LIMIT =>
-                    {
-                        msg.text.clear();
-                        msg.author_idx = 0;
-                        EventResult::Consumed(msgs)
-                    }
-                    _ => {
-                        msgs.push(Message {
-                            text: String::new(),
-                            author_idx: 0,
-                        });
-                        EventResult::Consumed(msgs)
-                    }
-                },
-                MessageEvent::FullNameExtracted(full_name) => {
-                    last_full_name.clear();
-                    last_full_name.push_str(full_name);
-                    EventResult::Consumed(msgs)
-                }
-                MessageEvent::ShortNameExtracted(short_name) if !names.contains(short_name) => {
-                    EventResult::SkipMessage(msgs)
-                }
-                MessageEvent::ShortNameExtracted(short_name) => {
-                    msgs.last_mut().unwrap().author_idx = authors
-                        .iter()
-                        .position(|a| a.short_name == short_name)
-                        .unwrap_or_else(|| {
-                            authors.push(Author {
-                                full_name: last_full_name.to_owned(),
-                                short_name: short_name.to_owned(),
-                            });
-                            authors.len() - 1
-                        });
-                    EventResult::Consumed(msgs)
-                }
-                MessageEvent::BodyPartExtracted(body) => {
-                    msgs.last_mut().unwrap().text.push_str(body);
-                    EventResult::Consumed(msgs)
+        let mut conversations: Vec<String> = Vec::new();
+        let mut seen: HashSet<(usize, NaiveDateTime, String)> = HashSet::new();
+        let mut texts: Vec<Message> = Vec::new();
+
+        for source in sources {
+            let conversation_idx = conversations.len();
+            conversations.push(source.name.to_owned());
+
+            let dump_texts = read_dump(source, names, &mut authors)
+                .into_iter()
+                .filter(|m| is_indexable(&m.text))
+                .map(|m| Message {
+                    text: redact::redact(&m.text).into_owned(),
+                    ..m
+                })
+                .filter(|m| seen.insert((m.author_idx, m.date, m.text.to_owned())))
+                .collect::<Vec<_>>();
+            for m in dump_texts {
+                for (i, part) in split_long_text(&m.text).into_iter().enumerate() {
+                    texts.push(Message {
+                        text: part.to_owned(),
+                        author_idx: m.author_idx,
+                        date: m.date,
+                        conversation_idx,
+                        continuation: i > 0,
+                    });
                 }
-                _ => EventResult::Consumed(msgs),
-            },
-        )
-        .unwrap();
+            }
+        }
 
-        let stemmer = Stemmer::new();
         let word_stem_to_text_idx = build_word_stem_to_text_idx(&texts, &stemmer);
+        let fuzzy_stems = fuzzy::FuzzyStems::new(word_stem_to_text_idx.keys().map(|s| s.as_str()));
 
         Self {
             authors,
+            conversations,
             texts,
             word_stem_to_text_idx,
+            fuzzy_stems,
             stemmer,
+            opted_out: HashSet::new(),
+        }
+    }
+
+    // Returns false if the message was filtered out
+    pub fn append(
+        &mut self,
+        conversation: &str,
+        short_name: &str,
+        full_name: &str,
+        text: &str,
+        date: NaiveDateTime,
+    ) -> bool {
+        if !is_indexable(text) {
+            return false;
+        }
+
+        let conversation_idx = self.conversation_idx(conversation).unwrap_or_else(|| {
+            self.conversations.push(conversation.to_owned());
+            self.conversations.len() - 1
+        });
+
+        let author_idx = self
+            .authors
+            .iter()
+            .position(|a| a.short_name == short_name)
+            .unwrap_or_else(|| {
+                self.authors.push(Author {
+                    full_name: full_name.to_owned(),
+                    short_name: short_name.to_owned(),
+                });
+                self.authors.len() - 1
+            });
+
+        let text = redact::redact(text);
+        for (i, part) in split_long_text(&text).into_iter().enumerate() {
+            let idx = self.texts.len() as u32;
+            for stem in split_text_into_stems(part, &self.stemmer) {
+                match self.word_stem_to_text_idx.get_mut(&stem) {
+                    Some(indexes) => {
+                        indexes.push(idx);
+                    }
+                    None => {
+                        self.fuzzy_stems.insert(&stem);
+                        self.word_stem_to_text_idx.insert(stem, vec![idx]);
+                    }
+                }
+            }
+            self.texts.push(Message {
+                text: part.to_owned(),
+                author_idx,
+                date,
+                conversation_idx,
+                continuation: i > 0,
+            });
+        }
+
+        true
+    }
+
+    pub fn set_opted_out(&mut self, short_name: &str, opted_out: bool) {
+        if opted_out {
+            self.opted_out.insert(short_name.to_owned());
+        } else {
+            self.opted_out.remove(short_name);
+        }
+    }
+
+    pub fn opted_out(&self) -> &HashSet<String> {
+        &self.opted_out
+    }
+
+    pub fn is_visible(&self, message: &Message) -> bool {
+        !self
+            .opted_out
+            .contains(&self.authors[message.author_idx].short_name)
+    }
+
+    pub fn visible_texts(&self) -> impl Iterator<Item = &Message> {
+        self.texts.iter().filter(move |m| self.is_visible(m))
+    }
+
+    pub fn conversation_idx(&self, name: &str) -> Option<usize> {
+        self.conversations.iter().position(|c| c == name)
+    }
+
+    pub fn index_of(&self, message: &Message) -> Option<usize> {
+        self.texts.iter().position(|m| std::ptr::eq(m, message))
+    }
+
+    pub fn full_message(&self, idx: usize) -> FullMessage<'_> {
+        let start = self.message_start(idx);
+        let mut end = idx + 1;
+        while end < self.texts.len() && self.texts[end].continuation {
+            end += 1;
+        }
+
+        let text = if end - start == 1 {
+            Cow::Borrowed(self.texts[start].text.as_str())
+        } else {
+            Cow::Owned(
+                self.texts[start..end]
+                    .iter()
+                    .map(|m| m.text.as_str())
+                    .collect(),
+            )
+        };
+        FullMessage {
+            author_idx: self.texts[start].author_idx,
+            date: self.texts[start].date,
+            text,
+            parts: start..end,
+        }
+    }
+
+    fn message_start(&self, idx: usize) -> usize {
+        let mut start = idx;
+        while start > 0 && self.texts[start].continuation {
+            start -= 1;
+        }
+        start
+    }
+
+    pub fn surrounding(&self, idx: usize, radius: usize) -> Vec<FullMessage<'_>> {
+        let quoted = self.full_message(idx);
+        let conversation_idx = self.texts[idx].conversation_idx;
+
+        let mut before = Vec::with_capacity(radius);
+        let mut start = quoted.parts.start;
+        while before.len() < radius
+            && start > 0
+            && self.texts[start - 1].conversation_idx == conversation_idx
+        {
+            let msg = self.full_message(start - 1);
+            start = msg.parts.start;
+            before.push(msg);
+        }
+        before.reverse();
+
+        let mut after = Vec::with_capacity(radius);
+        let mut end = quoted.parts.end;
+        while after.len() < radius
+            && end < self.texts.len()
+            && self.texts[end].conversation_idx == conversation_idx
+        {
+            let msg = self.full_message(end);
+            end = msg.parts.end;
+            after.push(msg);
         }
+
+        before
+            .into_iter()
+            .chain(std::iter::once(quoted))
+            .chain(after)
+            .collect()
     }
 
     pub fn containing_any_words<'p, P: Prompt>(&self, prompt: &'p P) -> Vec<&Message> {
@@ -98,6 +264,7 @@ impl MessageDump {
             .filter_map(move |s| self.word_stem_to_text_idx.get(s.as_ref()))
             .flatten()
             .map(|idx| &self.texts[*idx as usize])
+            .filter(|m| self.is_visible(m))
             .collect()
     }
 
@@ -112,20 +279,212 @@ impl MessageDump {
             })
             .collect::<Vec<HashSet<u32>>>();
 
+        self.containing_all_text_idxs(&stem_indexes)
+    }
+
+    // Long messages are counted once, by the index of their first part
+    pub fn occurrences<P: Prompt>(&self, prompt: &P) -> Vec<usize> {
+        let stem_indexes = prompt
+            .stems(&self.stemmer)
+            .iter()
+            .map(|s| match self.word_stem_to_text_idx.get(s.as_ref()) {
+                Some(idxs) => idxs.iter().copied().collect::<HashSet<u32>>(),
+                None => HashSet::new(),
+            })
+            .collect::<Vec<HashSet<u32>>>();
+        if stem_indexes.is_empty() {
+            return vec![];
+        }
+
+        let mut idxs = stem_indexes[0]
+            .iter()
+            .filter(|idx| stem_indexes.iter().all(|s| s.contains(idx)))
+            .map(|&idx| self.message_start(idx as usize))
+            .filter(|&idx| self.is_visible(&self.texts[idx]))
+            .collect::<Vec<_>>();
+        idxs.sort_unstable();
+        idxs.dedup();
+        idxs
+    }
+
+    pub fn stems<'t>(&'t self, text: &'t str) -> impl Iterator<Item = String> + 't {
+        split_text_into_stems(text, &self.stemmer)
+    }
+
+    pub fn containing_all_words_or_synonyms<P: Prompt>(&self, prompt: &P) -> Vec<&Message> {
+        let stem_indexes = prompt
+            .stems_with_synonyms(&self.stemmer)
+            .iter()
+            .map(|synonyms| {
+                synonyms
+                    .iter()
+                    .filter_map(|s| self.word_stem_to_text_idx.get(s.as_ref()))
+                    .flatten()
+                    .copied()
+                    .collect::<HashSet<u32>>()
+            })
+            .collect::<Vec<HashSet<u32>>>();
+
+        self.containing_all_text_idxs(&stem_indexes)
+    }
+
+    // Prompt words without exact matches are looked up with typos and transliteration
+    pub fn containing_any_words_fuzzy<P: Prompt>(&self, prompt: &P) -> Vec<&Message> {
+        prompt
+            .stems(&self.stemmer)
+            .iter()
+            .flat_map(|s| self.fuzzy_text_idxs(s.as_ref()))
+            .map(|idx| &self.texts[idx as usize])
+            .filter(|m| self.is_visible(m))
+            .collect()
+    }
+
+    pub fn containing_all_words_fuzzy<P: Prompt>(&self, prompt: &P) -> Vec<&Message> {
+        let stem_indexes = prompt
+            .stems(&self.stemmer)
+            .iter()
+            .map(|s| self.fuzzy_text_idxs(s.as_ref()).collect::<HashSet<u32>>())
+            .collect::<Vec<HashSet<u32>>>();
+
+        self.containing_all_text_idxs(&stem_indexes)
+    }
+
+    fn fuzzy_text_idxs<'s>(&'s self, stem: &'s str) -> impl Iterator<Item = u32> + 's {
+        let stems = if self.word_stem_to_text_idx.contains_key(stem) {
+            vec![stem]
+        } else {
+            self.fuzzy_stems.lookup(stem)
+        };
+        stems
+            .into_iter()
+            .filter_map(move |s| self.word_stem_to_text_idx.get(s))
+            .flatten()
+            .copied()
+    }
+
+    fn containing_all_text_idxs(&self, stem_indexes: &[HashSet<u32>]) -> Vec<&Message> {
         if stem_indexes.is_empty() {
             return vec![];
         }
 
         let mut messages_with_all_stems = Vec::new();
         for idx in &stem_indexes[0] {
-            if stem_indexes.iter().all(|s| s.contains(&idx)) {
-                messages_with_all_stems.push(&self.texts[*idx as usize]);
+            let message = &self.texts[*idx as usize];
+            if stem_indexes.iter().all(|s| s.contains(&idx)) && self.is_visible(message) {
+                messages_with_all_stems.push(message);
             }
         }
         messages_with_all_stems
     }
 }
 
+fn read_dump(
+    source: &DumpSource,
+    names: &HashSet<&str>,
+    authors: &mut Vec<Author>,
+) -> Vec<Message> {
+    let mut last_full_name: String = String::new();
+
+    fold_html(
+        source.path,
+        Vec::new(),
+        |mut msgs: Vec<Message>, event| match event {
+            MessageEvent::Start(_) => match msgs.last_mut() {
+                Some(msg) if !is_indexable(&msg.text) => {
+                    msg.text.clear();
+                    msg.author_idx = 0;
+                    msg.date = NaiveDateTime::from_timestamp(0, 0);
+                    EventResult::Consumed(msgs)
+                }
+                _ => {
+                    msgs.push(Message {
+                        text: String::new(),
+                        author_idx: 0,
+                        date: NaiveDateTime::from_timestamp(0, 0),
+                        conversation_idx: 0,
+                        continuation: false,
+                    });
+                    EventResult::Consumed(msgs)
+                }
+            },
+            MessageEvent::FullNameExtracted(full_name) => {
+                last_full_name.clear();
+                last_full_name.push_str(full_name);
+                EventResult::Consumed(msgs)
+            }
+            MessageEvent::ShortNameExtracted(short_name) => {
+                let short_name = source
+                    .short_names
+                    .get(short_name)
+                    .map(|s| s.as_str())
+                    .unwrap_or(short_name);
+                if !names.contains(short_name) {
+                    return EventResult::SkipMessage(msgs);
+                }
+                msgs.last_mut().unwrap().author_idx = authors
+                    .iter()
+                    .position(|a| a.short_name == short_name)
+                    .unwrap_or_else(|| {
+                        authors.push(Author {
+                            full_name: last_full_name.to_owned(),
+                            short_name: short_name.to_owned(),
+                        });
+                        authors.len() - 1
+                    });
+                EventResult::Consumed(msgs)
+            }
+            MessageEvent::DateExtracted(date) => {
+                msgs.last_mut().unwrap().date =
+                    NaiveDateTime::parse_from_str(date, "%Y.%m.%d %H:%M:%S").unwrap();
+                EventResult::Consumed(msgs)
+            }
+            MessageEvent::BodyPartExtracted(body) => {
+                msgs.last_mut().unwrap().text.push_str(body);
+                EventResult::Consumed(msgs)
+            }
+            _ => EventResult::Consumed(msgs),
+        },
+    )
+    .unwrap_or_else(|e| panic!("Cannot read {}: {:?}", source.path, e))
+}
+
+fn is_indexable(text: &str) -> bool {
+    !text.trim().is_empty()
+}
+
+// Cuts at paragraphs where possible, and at sentences or words otherwise
+fn split_long_text(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut rest = text;
+    while let Some((window_end, _)) = rest.char_indices().nth(DISCORD_TEXT_LIMIT - 1) {
+        let window = &rest[..window_end];
+        // Avoid cutting off tiny parts when the only boundary is close to the start
+        let min_cut = window_end / 2;
+
+        let paragraph_cut = window.rfind('\n').map(|i| i + 1);
+        let sentence_cut = window
+            .char_indices()
+            .rev()
+            .find(|&(_, c)| c == '.' || c == '!' || c == '?' || c == '…')
+            .map(|(i, c)| i + c.len_utf8());
+        let word_cut = window.rfind(' ').map(|i| i + 1);
+
+        let cut = [paragraph_cut, sentence_cut, word_cut]
+            .iter()
+            .flatten()
+            .copied()
+            .find(|&cut| cut >= min_cut)
+            .unwrap_or(window_end);
+
+        parts.push(&rest[..cut]);
+        rest = &rest[cut..];
+    }
+    if !rest.trim().is_empty() {
+        parts.push(rest);
+    }
+    parts
+}
+
 fn build_word_stem_to_text_idx(texts: &[Message], stemmer: &Stemmer) -> HashMap<String, Vec<u32>> {
     let mut map: HashMap<String, Vec<u32>> = HashMap::new();
 
@@ -146,8 +505,10 @@ fn build_word_stem_to_text_idx(texts: &[Message], stemmer: &Stemmer) -> HashMap<
 }
 
 pub struct Stemmer {
-    ru_stemmer: rust_stemmers::Stemmer,
-    en_stemmer: rust_stemmers::Stemmer,
+    languages: Vec<Language>,
+    stemmers: HashMap<Language, rust_stemmers::Stemmer>,
+    stopwords: HashSet<String>,
+    synonyms: HashMap<String, Vec<String>>,
 }
 
 impl std::fmt::Debug for Stemmer {
@@ -157,26 +518,83 @@ impl std::fmt::Debug for Stemmer {
 }
 
 impl Stemmer {
-    fn new() -> Self {
-        let ru_stemmer = rust_stemmers::Stemmer::create(rust_stemmers::Algorithm::Russian);
-        let en_stemmer = rust_stemmers::Stemmer::create(rust_stemmers::Algorithm::English);
-        Self {
-            ru_stemmer,
-            en_stemmer,
+    pub fn new(
+        languages: &[Language],
+        stopwords: HashSet<String>,
+        synonym_groups: Vec<Vec<String>>,
+    ) -> Self {
+        let stemmers = languages
+            .iter()
+            .filter_map(|&l| {
+                l.algorithm()
+                    .map(|a| (l, rust_stemmers::Stemmer::create(a)))
+            })
+            .collect();
+        let mut stemmer = Self {
+            languages: languages.to_vec(),
+            stemmers,
+            stopwords,
+            synonyms: HashMap::new(),
+        };
+
+        for group in synonym_groups {
+            let mut stems = group
+                .iter()
+                .map(|w| stemmer.stem(&w.to_lowercase()).into_owned())
+                .collect::<Vec<_>>();
+            stems.sort();
+            stems.dedup();
+            for stem in &stems {
+                let synonyms = stemmer.synonyms.entry(stem.to_owned()).or_default();
+                for other in stems.iter().filter(|&s| s != stem) {
+                    if !synonyms.contains(other) {
+                        synonyms.push(other.to_owned());
+                    }
+                }
+            }
         }
+
+        stemmer
+    }
+
+    pub fn is_stopword(&self, input: &str) -> bool {
+        self.stopwords.contains(input)
+    }
+
+    pub fn synonyms(&self, stem: &str) -> &[String] {
+        self.synonyms.get(stem).map(|s| s.as_slice()).unwrap_or(&[])
     }
 
     pub fn stem<'a>(&self, input: &'a str) -> Cow<'a, str> {
-        if input.is_ascii() {
-            self.en_stemmer.stem(&input)
-        } else {
-            self.ru_stemmer.stem(&input)
+        if let Some(ru_stemmer) = self.stemmers.get(&Language::Russian) {
+            if language::looks_transliterated(input) {
+                let cyrillic = language::cyrillize(input);
+                return Cow::Owned(ru_stemmer.stem(&cyrillic).into_owned());
+            }
+        }
+        match language::detect(input, &self.languages).and_then(|l| self.stemmers.get(&l)) {
+            Some(stemmer) => stemmer.stem(input),
+            None => Cow::Borrowed(input),
         }
     }
 }
 
 pub trait Prompt {
     fn stems<'a>(&'a self, stemmer: &Stemmer) -> Vec<Cow<'a, str>>;
+
+    // Prompt stems, each followed by the stems of its synonyms
+    fn stems_with_synonyms<'a>(&'a self, stemmer: &'a Stemmer) -> Vec<Vec<Cow<'a, str>>> {
+        self.stems(stemmer)
+            .into_iter()
+            .map(|stem| {
+                let synonyms = stemmer
+                    .synonyms(&stem)
+                    .iter()
+                    .map(|s| Cow::Borrowed(s.as_str()));
+                std::iter::once(stem).chain(synonyms).collect()
+            })
+            .collect()
+    }
 }
 
 impl Prompt for &str {
@@ -196,14 +614,224 @@ impl Prompt for &[&str] {
 fn split_text_into_stems<'t>(
     text: &'t str,
     stemmer: &'t Stemmer,
+) -> impl Iterator<Item = String> + 't {
+    split_text_into_words(text, stemmer).map(move |word| stemmer.stem(&word).into_owned())
+}
+
+fn split_text_into_words<'t>(
+    text: &'t str,
+    stemmer: &'t Stemmer,
 ) -> impl Iterator<Item = String> + 't {
     text.split(&[' ', '\n', '.', '…', ',', '!', '?', '(', ')', '[', ']', '/', '|', '@', '"', ':', '-', '+'][..])
         .filter_map(move |w| {
-            let word = w.trim();
-            if word.is_empty() {
+            let word = w.trim().to_lowercase();
+            if word.is_empty() || stemmer.is_stopword(&word) {
                 None
             } else {
-                Some(stemmer.stem(&w.to_lowercase()).into_owned())
+                Some(word)
             }
         })
 }
+
+#[cfg(test)]
+pub mod fixtures {
+    use super::*;
+    use chrono::NaiveDate;
+
+    pub fn message(author_idx: usize, text: &str, year: i32, month: u32) -> Message {
+        Message {
+            text: text.into(),
+            author_idx,
+            date: NaiveDate::from_ymd(year, month, 1).and_hms(12, 0, 0),
+            conversation_idx: 0,
+            continuation: false,
+        }
+    }
+
+    pub fn dump(short_names: &[&str], conversations: &[&str], texts: Vec<Message>) -> MessageDump {
+        let stemmer = Stemmer::new(
+            &[Language::Russian, Language::English],
+            HashSet::new(),
+            vec![],
+        );
+        let word_stem_to_text_idx = build_word_stem_to_text_idx(&texts, &stemmer);
+        let fuzzy_stems = fuzzy::FuzzyStems::new(word_stem_to_text_idx.keys().map(|s| s.as_str()));
+        MessageDump {
+            authors: short_names
+                .iter()
+                .map(|&name| Author {
+                    short_name: name.into(),
+                    full_name: name.into(),
+                })
+                .collect(),
+            conversations: conversations.iter().map(|&c| c.into()).collect(),
+            texts,
+            word_stem_to_text_idx,
+            fuzzy_stems,
+            stemmer,
+            opted_out: HashSet::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_split_long_text() {
+        assert_eq!(split_long_text("short"), vec!["short"]);
+
+        let paragraph = "слово ".repeat(200);
+        let text = format!("{}\n{}\n{}", paragraph, paragraph, paragraph);
+        let parts = split_long_text(&text);
+        assert_eq!(parts.len(), 3);
+        assert!(parts[0].ends_with('\n'));
+        assert!(parts.iter().all(|p| p.chars().count() < DISCORD_TEXT_LIMIT));
+        assert_eq!(parts.concat(), text);
+
+        let unbroken = "а".repeat(DISCORD_TEXT_LIMIT * 2);
+        let parts = split_long_text(&unbroken);
+        assert_eq!(parts.len(), 3);
+        assert_eq!(parts.concat(), unbroken);
+    }
+
+    #[test]
+    fn test_surrounding() {
+        let mut split_end = fixtures::message(1, "вторая часть", 2020, 1);
+        split_end.continuation = true;
+        let mut side_message = fixtures::message(0, "другая беседа", 2020, 1);
+        side_message.conversation_idx = 1;
+        let messages = fixtures::dump(
+            &["денко", "вова"],
+            &["main", "side"],
+            vec![
+                fixtures::message(0, "привет", 2020, 1),
+                fixtures::message(1, "первая часть ", 2020, 1),
+                split_end,
+                fixtures::message(0, "ответ", 2020, 1),
+                fixtures::message(1, "еще", 2020, 1),
+                side_message,
+            ],
+        );
+        fn texts_of(surrounding: Vec<FullMessage>) -> Vec<String> {
+            surrounding
+                .into_iter()
+                .map(|m| m.text.into_owned())
+                .collect()
+        }
+
+        for idx in 1..3 {
+            let full = messages.full_message(idx);
+            assert_eq!(full.text, "первая часть вторая часть");
+            assert_eq!(full.parts, 1..3);
+            assert_eq!(full.author_idx, 1);
+        }
+        assert_eq!(
+            texts_of(messages.surrounding(3, 1)),
+            vec!["первая часть вторая часть", "ответ", "еще"]
+        );
+        assert_eq!(
+            texts_of(messages.surrounding(2, 5)),
+            vec!["привет", "первая часть вторая часть", "ответ", "еще"]
+        );
+        assert_eq!(texts_of(messages.surrounding(5, 1)), vec!["другая беседа"]);
+    }
+
+    #[test]
+    fn test_synonyms() {
+        let groups = vec![vec![
+            "Кот".into(),
+            "котик".into(),
+            "кошка".into(),
+            "cat".into(),
+        ]];
+        let stemmer = Stemmer::new(
+            &[Language::Russian, Language::English],
+            HashSet::new(),
+            groups,
+        );
+        let stems = "котиках".stems_with_synonyms(&stemmer);
+        assert_eq!(stems.len(), 1);
+        assert_eq!(stems[0][0], "котик");
+        let mut synonyms = stems[0][1..].to_vec();
+        synonyms.sort();
+        assert_eq!(synonyms, vec!["cat", "кот", "кошк"]);
+        assert!(stemmer.synonyms("собак").is_empty());
+    }
+
+    #[test]
+    fn test_containing_all_words_or_synonyms() {
+        let mut messages = fixtures::dump(
+            &["денко"],
+            &["main"],
+            vec![
+                fixtures::message(0, "котик спит на диване", 2020, 1),
+                fixtures::message(0, "кот ест", 2020, 1),
+            ],
+        );
+        messages.stemmer = Stemmer::new(
+            &[Language::Russian],
+            HashSet::new(),
+            vec![vec!["кот".into(), "котик".into()]],
+        );
+
+        assert_eq!(
+            messages
+                .containing_all_words_or_synonyms(&"кот на диване")
+                .len(),
+            1
+        );
+        assert_eq!(messages.containing_all_words_or_synonyms(&"кот").len(), 2);
+        // Every word has to be there, an unknown one doesn't just drop out
+        assert!(messages
+            .containing_all_words_or_synonyms(&"кот жираф")
+            .is_empty());
+    }
+
+    #[test]
+    fn test_containing_all_words_fuzzy() {
+        let messages = fixtures::dump(
+            &["денко"],
+            &["main"],
+            vec![
+                fixtures::message(0, "пью жасминовый чай", 2020, 1),
+                fixtures::message(0, "чай остыл", 2020, 1),
+            ],
+        );
+        let typos: &[&str] = &["жасмнов", "чаи"];
+        assert_eq!(messages.containing_all_words_fuzzy(&typos).len(), 1);
+        let unknown: &[&str] = &["чаи", "кофе"];
+        assert!(messages.containing_all_words_fuzzy(&unknown).is_empty());
+    }
+
+    #[test]
+    fn test_append() {
+        let mut messages = fixtures::dump(
+            &["денко"],
+            &["main"],
+            vec![fixtures::message(0, "чай остыл", 2020, 1)],
+        );
+        let date = NaiveDate::from_ymd(2021, 1, 1).and_hms(12, 0, 0);
+
+        assert!(!messages.append("main", "вова", "Вова", "  ", date));
+        assert!(messages.append("main", "вова", "Вова", "пью жасминовый чай", date));
+        let found = messages.containing_all_words(&"жасминовый");
+        assert_eq!(found.len(), 1);
+        assert_eq!(messages.authors[found[0].author_idx].short_name, "вова");
+        let typos: &[&str] = &["жасмнов", "чаи"];
+        assert_eq!(messages.containing_all_words_fuzzy(&typos).len(), 1);
+
+        let paragraph = "слово ".repeat(200);
+        let long_text = format!("{}\n{}\n{}", paragraph, paragraph, paragraph);
+        assert!(messages.append("discord", "денко", "Денко", &long_text, date));
+        let parts = &messages.texts[2..];
+        assert_eq!(
+            parts.iter().map(|m| m.continuation).collect::<Vec<_>>(),
+            vec![false, true, true]
+        );
+        assert!(parts.iter().all(|m| m.conversation_idx == 1));
+        assert_eq!(messages.full_message(4).text, long_text);
+    }
+}
@@ -0,0 +1,199 @@
+// A BK-tree keyed by Latin transliteration, so that "итм" and "itm" end up in the same node
+#[derive(Debug)]
+pub struct FuzzyStems {
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug)]
+struct Node {
+    key: Vec<char>,
+    stems: Vec<String>,
+    children: Vec<(usize, usize)>, // (distance to key, node index)
+}
+
+impl FuzzyStems {
+    pub fn new<'s>(stems: impl Iterator<Item = &'s str>) -> Self {
+        let mut tree = Self { nodes: Vec::new() };
+        for stem in stems {
+            tree.insert(stem);
+        }
+        tree
+    }
+
+    pub fn insert(&mut self, stem: &str) {
+        let key = transliterate(stem).chars().collect::<Vec<_>>();
+        if self.nodes.is_empty() {
+            self.nodes.push(Node::new(key, stem));
+            return;
+        }
+
+        let mut idx = 0;
+        loop {
+            let dist = levenshtein(&self.nodes[idx].key, &key);
+            if dist == 0 {
+                let stems = &mut self.nodes[idx].stems;
+                if !stems.iter().any(|s| s == stem) {
+                    stems.push(stem.to_owned());
+                }
+                return;
+            }
+            match self.nodes[idx].children.iter().find(|(d, _)| *d == dist) {
+                Some(&(_, child_idx)) => idx = child_idx,
+                None => {
+                    let child_idx = self.nodes.len();
+                    self.nodes.push(Node::new(key, stem));
+                    self.nodes[idx].children.push((dist, child_idx));
+                    return;
+                }
+            }
+        }
+    }
+
+    pub fn lookup(&self, stem: &str) -> Vec<&str> {
+        if self.nodes.is_empty() {
+            return vec![];
+        }
+        let key = transliterate(stem).chars().collect::<Vec<_>>();
+        let max_dist = max_distance(key.len());
+
+        let mut best_dist = max_dist;
+        let mut matches: Vec<&Node> = Vec::new();
+        let mut queue = vec![0];
+        while let Some(idx) = queue.pop() {
+            let node = &self.nodes[idx];
+            let dist = levenshtein(&node.key, &key);
+            if dist < best_dist {
+                best_dist = dist;
+                matches.clear();
+            }
+            if dist == best_dist {
+                matches.push(node);
+            }
+            for &(child_dist, child_idx) in &node.children {
+                if child_dist + best_dist >= dist && child_dist <= dist + best_dist {
+                    queue.push(child_idx);
+                }
+            }
+        }
+        matches
+            .into_iter()
+            .flat_map(|n| n.stems.iter().map(|s| s.as_str()))
+            .collect()
+    }
+}
+
+impl Node {
+    fn new(key: Vec<char>, stem: &str) -> Self {
+        Self {
+            key,
+            stems: vec![stem.to_owned()],
+            children: Vec::new(),
+        }
+    }
+}
+
+fn max_distance(key_len: usize) -> usize {
+    match key_len {
+        0..=2 => 0,
+        3..=5 => 1,
+        _ => 2,
+    }
+}
+
+fn transliterate(word: &str) -> String {
+    let mut latin = String::with_capacity(word.len());
+    for c in word.chars() {
+        let replacement = match c {
+            'а' => "a",
+            'б' => "b",
+            'в' => "v",
+            'г' => "g",
+            'д' => "d",
+            'е' | 'ё' | 'э' => "e",
+            'ж' => "zh",
+            'з' => "z",
+            'и' => "i",
+            'й' | 'ы' => "y",
+            'к' => "k",
+            'л' => "l",
+            'м' => "m",
+            'н' => "n",
+            'о' => "o",
+            'п' => "p",
+            'р' => "r",
+            'с' => "s",
+            'т' => "t",
+            'у' => "u",
+            'ф' => "f",
+            'х' => "h",
+            'ц' => "ts",
+            'ч' => "ch",
+            'ш' => "sh",
+            'щ' => "sch",
+            'ъ' | 'ь' => "",
+            'ю' => "yu",
+            'я' => "ya",
+            _ => {
+                latin.push(c);
+                continue;
+            }
+        };
+        latin.push_str(replacement);
+    }
+    latin
+}
+
+fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.iter().enumerate() {
+        let mut diag = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let subst = if ca == cb { diag } else { diag + 1 };
+            diag = row[j + 1];
+            row[j + 1] = std::cmp::min(subst, std::cmp::min(row[j], row[j + 1]) + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transliteration_equivalence() {
+        let tree = FuzzyStems::new(vec!["итм", "бонч", "кот"].into_iter());
+        assert_eq!(tree.lookup("itm"), vec!["итм"]);
+        assert_eq!(tree.lookup("bonch"), vec!["бонч"]);
+    }
+
+    #[test]
+    fn test_typos() {
+        let tree = FuzzyStems::new(vec!["итм", "бонч", "жасминов", "чай"].into_iter());
+        assert_eq!(tree.lookup("itmo"), vec!["итм"]);
+        assert_eq!(tree.lookup("жасмнов"), vec!["жасминов"]);
+        assert_eq!(tree.lookup("чаи"), vec!["чай"]);
+        assert_eq!(tree.lookup("кофе"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn test_closest_matches_only() {
+        let tree = FuzzyStems::new(vec!["котик", "кот", "коты"].into_iter());
+        assert_eq!(tree.lookup("кот"), vec!["кот"]);
+        assert_eq!(tree.lookup("kotiki"), vec!["котик"]);
+    }
+
+    #[test]
+    fn test_levenshtein() {
+        let dist = |a: &str, b: &str| {
+            levenshtein(
+                &a.chars().collect::<Vec<_>>(),
+                &b.chars().collect::<Vec<_>>(),
+            )
+        };
+        assert_eq!(dist("", ""), 0);
+        assert_eq!(dist("kitten", "sitting"), 3);
+        assert_eq!(dist("itm", "itmo"), 1);
+    }
+}
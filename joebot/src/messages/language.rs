@@ -0,0 +1,179 @@
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    Russian,
+    Ukrainian,
+    English,
+    German,
+    French,
+    Spanish,
+}
+
+impl Language {
+    // Words of languages without an algorithm are left unstemmed
+    pub fn algorithm(self) -> Option<rust_stemmers::Algorithm> {
+        match self {
+            Language::Russian => Some(rust_stemmers::Algorithm::Russian),
+            Language::Ukrainian => None,
+            Language::English => Some(rust_stemmers::Algorithm::English),
+            Language::German => Some(rust_stemmers::Algorithm::German),
+            Language::French => Some(rust_stemmers::Algorithm::French),
+            Language::Spanish => Some(rust_stemmers::Algorithm::Spanish),
+        }
+    }
+
+    fn has_letter(self, c: char) -> bool {
+        match self {
+            Language::Russian => ('а'..='я').contains(&c) || c == 'ё',
+            Language::Ukrainian => {
+                (('а'..='я').contains(&c) && !"ыэъ".contains(c)) || "іїєґ'".contains(c)
+            }
+            Language::English => c.is_ascii_lowercase(),
+            Language::German => c.is_ascii_lowercase() || "äöüß".contains(c),
+            Language::French => c.is_ascii_lowercase() || "àâæçéèêëîïôœùûüÿ".contains(c),
+            Language::Spanish => c.is_ascii_lowercase() || "áéíñóúü".contains(c),
+        }
+    }
+
+    fn is_distinctive(self, c: char) -> bool {
+        match self {
+            Language::Russian => "ыэъё".contains(c),
+            Language::Ukrainian => "іїєґ".contains(c),
+            Language::English => false,
+            Language::German => "äöüß".contains(c),
+            Language::French => "àâæçèêëîïôœùûÿ".contains(c),
+            Language::Spanish => "áíñóú".contains(c),
+        }
+    }
+}
+
+// Prefers languages whose distinctive letters are present over the ones listed first
+pub fn detect(word: &str, languages: &[Language]) -> Option<Language> {
+    let letters = word
+        .chars()
+        .filter(|c| c.is_alphabetic())
+        .collect::<Vec<_>>();
+    if letters.is_empty() {
+        return None;
+    }
+    let mut candidates = languages
+        .iter()
+        .copied()
+        .filter(|l| letters.iter().all(|&c| l.has_letter(c)));
+
+    let first_candidate = candidates.next()?;
+    if letters.iter().any(|&c| first_candidate.is_distinctive(c)) {
+        return Some(first_candidate);
+    }
+    candidates
+        .find(|l| letters.iter().any(|&c| l.is_distinctive(c)))
+        .or(Some(first_candidate))
+}
+
+pub fn looks_transliterated(word: &str) -> bool {
+    const DIGRAPHS: [&str; 4] = ["zh", "kh", "shch", "yy"];
+    const SUFFIXES: [&str; 6] = ["iy", "ogo", "ego", "ykh", "ikh", "ami"];
+
+    word.chars().all(|c| c.is_ascii_lowercase())
+        && (DIGRAPHS.iter().any(|d| word.contains(d))
+            || SUFFIXES
+                .iter()
+                .any(|s| word.len() > s.len() + 1 && word.ends_with(s)))
+}
+
+pub fn cyrillize(word: &str) -> String {
+    const DIGRAPHS: [(&str, &str); 13] = [
+        ("shch", "щ"),
+        ("sch", "щ"),
+        ("zh", "ж"),
+        ("kh", "х"),
+        ("ts", "ц"),
+        ("ch", "ч"),
+        ("sh", "ш"),
+        ("yu", "ю"),
+        ("ya", "я"),
+        ("yo", "ё"),
+        ("iy", "ий"),
+        ("yy", "ый"),
+        ("x", "кс"),
+    ];
+
+    let mut cyrillic = String::with_capacity(word.len() * 2);
+    let mut rest = word;
+    'outer: while let Some(c) = rest.chars().next() {
+        for (latin, replacement) in DIGRAPHS.iter() {
+            if rest.starts_with(latin) {
+                cyrillic.push_str(replacement);
+                rest = &rest[latin.len()..];
+                continue 'outer;
+            }
+        }
+        cyrillic.push(match c {
+            'a' => 'а',
+            'b' => 'б',
+            'v' | 'w' => 'в',
+            'g' => 'г',
+            'd' => 'д',
+            'e' => 'е',
+            'z' => 'з',
+            'i' => 'и',
+            'j' => 'й',
+            'k' | 'c' | 'q' => 'к',
+            'l' => 'л',
+            'm' => 'м',
+            'n' => 'н',
+            'o' => 'о',
+            'p' => 'п',
+            'r' => 'р',
+            's' => 'с',
+            't' => 'т',
+            'u' => 'у',
+            'f' => 'ф',
+            'h' => 'х',
+            'y' => 'ы',
+            other => other,
+        });
+        rest = &rest[c.len_utf8()..];
+    }
+    cyrillic
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LANGUAGES: [Language; 4] = [
+        Language::Russian,
+        Language::Ukrainian,
+        Language::English,
+        Language::German,
+    ];
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(detect("привет", &LANGUAGES), Some(Language::Russian));
+        assert_eq!(detect("вы", &LANGUAGES), Some(Language::Russian));
+        assert_eq!(detect("їжак", &LANGUAGES), Some(Language::Ukrainian));
+        assert_eq!(detect("hello", &LANGUAGES), Some(Language::English));
+        assert_eq!(detect("größe", &LANGUAGES), Some(Language::German));
+        assert_eq!(detect("2к19", &LANGUAGES), Some(Language::Russian));
+        assert_eq!(detect("42", &LANGUAGES), None);
+        assert_eq!(detect("привет", &[Language::English]), None);
+    }
+
+    #[test]
+    fn test_transliteration() {
+        assert!(looks_transliterated("khorosho"));
+        assert!(looks_transliterated("zhizn"));
+        assert!(looks_transliterated("krasniy"));
+        assert!(!looks_transliterated("hello"));
+        assert!(!looks_transliterated("ami"));
+
+        assert_eq!(cyrillize("khorosho"), "хорошо");
+        assert_eq!(cyrillize("zhizn"), "жизн");
+        assert_eq!(cyrillize("krasniy"), "красний");
+        assert_eq!(cyrillize("shchuka"), "щука");
+    }
+}
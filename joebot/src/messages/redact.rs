@@ -0,0 +1,79 @@
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use std::borrow::Cow;
+
+const REDACTED: &str = "[скрыто]";
+
+lazy_static! {
+    // Card-like numbers: 13 to 19 digits, optionally grouped with spaces or dashes
+    static ref CARD_PATTERN: Regex = Regex::new(r"\b\d(?:[ -]?\d){12,18}\b").unwrap();
+    static ref PATTERNS: [Regex; 2] = [
+        // Phone numbers: +7 (999) 123-45-67, 89991234567, +44 20 1234 5678
+        Regex::new(r"(?:\+\d{1,3}[\s-]?|\b8[\s-]?)\(?\d{2,4}\)?(?:[\s-]?\d){6,8}\b").unwrap(),
+        Regex::new(r"[\w.+-]+@[\w-]+(?:\.[\w-]+)+").unwrap(),
+    ];
+}
+
+pub fn redact(text: &str) -> Cow<'_, str> {
+    // Lists of years and other numbers look like cards too, but don't pass the checksum
+    let mut redacted = CARD_PATTERN.replace_all(text, |c: &Captures| {
+        if passes_luhn(&c[0]) {
+            REDACTED.to_owned()
+        } else {
+            c[0].to_owned()
+        }
+    });
+    for pattern in PATTERNS.iter() {
+        let replaced = match pattern.replace_all(&redacted, REDACTED) {
+            Cow::Owned(replaced) => replaced,
+            Cow::Borrowed(_) => continue,
+        };
+        redacted = Cow::Owned(replaced);
+    }
+    redacted
+}
+
+fn passes_luhn(number: &str) -> bool {
+    let checksum: u32 = number
+        .chars()
+        .rev()
+        .filter_map(|c| c.to_digit(10))
+        .enumerate()
+        .map(|(i, d)| match (i % 2, d * 2) {
+            (0, _) => d,
+            (_, doubled) if doubled > 9 => doubled - 9,
+            (_, doubled) => doubled,
+        })
+        .sum();
+    checksum.is_multiple_of(10)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact() {
+        assert_eq!(
+            redact("звони +7 (999) 123-45-67 или 89991234567"),
+            "звони [скрыто] или [скрыто]"
+        );
+        assert_eq!(redact("пиши на joe@saloon.com"), "пиши на [скрыто]");
+        assert_eq!(
+            redact("карта 4111 1111 1111 1111, срок 12/24"),
+            "карта [скрыто], срок 12/24"
+        );
+        assert_eq!(
+            redact("в 2019 году 150 человек"),
+            Cow::Borrowed("в 2019 году 150 человек")
+        );
+        assert_eq!(
+            redact("были в 2016 2017 2018 2019 годах"),
+            "были в 2016 2017 2018 2019 годах"
+        );
+        assert_eq!(
+            redact("1 2 3 4 5 6 7 8 9 10 11 12 13"),
+            "1 2 3 4 5 6 7 8 9 10 11 12 13"
+        );
+    }
+}
@@ -0,0 +1,174 @@
+use super::{split_text_into_words, Message, MessageDump};
+use std::collections::HashMap;
+
+// Added to every count, so that words one side never uses don't get infinite odds
+const PRIOR: f64 = 0.5;
+const MIN_USES: usize = 5;
+
+#[derive(Debug)]
+pub struct SignatureWord {
+    pub stem: String,
+    pub word: String, // the form the author writes most often
+    pub uses: usize,
+    pub ratio: f64, // frequency in the author's messages vs everyone else's
+}
+
+impl MessageDump {
+    // Z-scored log-odds ratio against the rest of the visible messages
+    pub fn signature_words(&self, author_idx: usize, limit: usize) -> Vec<SignatureWord> {
+        let mut counts = Vec::new();
+        let (mut total, mut others_total) = (0, 0);
+        for (stem, idxs) in &self.word_stem_to_text_idx {
+            let (mut uses, mut others_uses) = (0, 0);
+            for &idx in idxs {
+                let m = &self.texts[idx as usize];
+                if !self.is_visible(m) {
+                    continue;
+                }
+                if m.author_idx == author_idx {
+                    uses += 1;
+                } else {
+                    others_uses += 1;
+                }
+            }
+            total += uses;
+            others_total += others_uses;
+            if uses >= MIN_USES && is_word(stem) {
+                counts.push((stem, uses, others_uses));
+            }
+        }
+
+        let mut scored = counts
+            .into_iter()
+            .map(|(stem, uses, others_uses)| {
+                let score = log_odds_z(uses, total, others_uses, others_total);
+                (stem, uses, others_uses, score)
+            })
+            .filter(|&(_, _, _, score)| score > 0.0)
+            .collect::<Vec<_>>();
+        scored.sort_by(|a, b| b.3.total_cmp(&a.3).then_with(|| a.0.cmp(b.0)));
+        scored.truncate(limit);
+
+        let mut forms = self.surface_forms(
+            |m| m.author_idx == author_idx,
+            scored.iter().map(|s| s.0.as_str()),
+        );
+        scored
+            .into_iter()
+            .map(|(stem, uses, others_uses, _)| SignatureWord {
+                stem: stem.to_owned(),
+                word: forms
+                    .remove(stem.as_str())
+                    .unwrap_or_else(|| stem.to_owned()),
+                uses,
+                ratio: frequency_ratio(uses, total, others_uses, others_total),
+            })
+            .collect()
+    }
+
+    // Most frequent words of the visible messages, in their most common spelling
+    pub fn word_frequencies<F>(&self, filter: F, limit: usize) -> Vec<(String, usize)>
+    where
+        F: Fn(&Message) -> bool,
+    {
+        let mut counts = self
+            .word_stem_to_text_idx
+            .iter()
+            .filter(|(stem, _)| is_word(stem))
+            .map(|(stem, idxs)| {
+                let uses = idxs
+                    .iter()
+                    .map(|&idx| &self.texts[idx as usize])
+                    .filter(|m| filter(m) && self.is_visible(m))
+                    .count();
+                (stem, uses)
+            })
+            .filter(|&(_, uses)| uses > 0)
+            .collect::<Vec<_>>();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        counts.truncate(limit);
+
+        let mut forms = self.surface_forms(&filter, counts.iter().map(|c| c.0.as_str()));
+        counts
+            .into_iter()
+            .map(|(stem, uses)| {
+                let word = forms
+                    .remove(stem.as_str())
+                    .unwrap_or_else(|| stem.to_owned());
+                (word, uses)
+            })
+            .collect()
+    }
+
+    fn surface_forms<'s, F>(
+        &self,
+        filter: F,
+        stems: impl Iterator<Item = &'s str>,
+    ) -> HashMap<&'s str, String>
+    where
+        F: Fn(&Message) -> bool,
+    {
+        let mut form_counts: HashMap<&str, HashMap<String, usize>> =
+            stems.map(|s| (s, HashMap::new())).collect();
+        for m in self.visible_texts().filter(|m| filter(m)) {
+            for word in split_text_into_words(&m.text, &self.stemmer) {
+                if let Some(forms) = form_counts.get_mut(self.stemmer.stem(&word).as_ref()) {
+                    *forms.entry(word).or_insert(0) += 1;
+                }
+            }
+        }
+
+        form_counts
+            .into_iter()
+            .filter_map(|(stem, forms)| {
+                let word = forms
+                    .into_iter()
+                    .max_by(|(a, a_count), (b, b_count)| a_count.cmp(b_count).then(b.cmp(a)))?
+                    .0;
+                Some((stem, word))
+            })
+            .collect()
+    }
+}
+
+fn is_word(stem: &str) -> bool {
+    stem.chars().count() > 1 && stem.chars().all(char::is_alphabetic)
+}
+
+fn log_odds_z(uses: usize, total: usize, others_uses: usize, others_total: usize) -> f64 {
+    let (a, n_a) = (uses as f64 + PRIOR, total as f64 + 2.0 * PRIOR);
+    let (b, n_b) = (
+        others_uses as f64 + PRIOR,
+        others_total as f64 + 2.0 * PRIOR,
+    );
+    let delta = (a / (n_a - a)).ln() - (b / (n_b - b)).ln();
+    let variance = 1.0 / a + 1.0 / (n_a - a) + 1.0 / b + 1.0 / (n_b - b);
+    delta / variance.sqrt()
+}
+
+fn frequency_ratio(uses: usize, total: usize, others_uses: usize, others_total: usize) -> f64 {
+    let own = (uses as f64 + PRIOR) / (total as f64 + PRIOR);
+    let others = (others_uses as f64 + PRIOR) / (others_total as f64 + PRIOR);
+    own / others
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_odds_z() {
+        // Same relative frequency on both sides
+        assert!(log_odds_z(10, 1000, 100, 10000).abs() < 0.5);
+        // Used more often by the author
+        assert!(log_odds_z(50, 1000, 100, 10000) > 3.0);
+        assert!(log_odds_z(1, 1000, 500, 10000) < 0.0);
+        // The same ratio is more telling when backed by more uses
+        assert!(log_odds_z(100, 2000, 100, 10000) > log_odds_z(10, 200, 10, 1000));
+
+        assert!((frequency_ratio(50, 1000, 100, 10000) - 5.0).abs() < 0.1);
+        assert!(is_word("кринж"));
+        assert!(!is_word("2019"));
+        assert!(!is_word("я"));
+    }
+}
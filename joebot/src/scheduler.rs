@@ -0,0 +1,15 @@
+use crate::commands::CommandDispatcher;
+use chrono::Local;
+use serenity::{http::Http, prelude::Mutex};
+use std::sync::Arc;
+use std::time::Duration;
+
+const TICK_INTERVAL: Duration = Duration::from_secs(60);
+
+pub fn start(dispatcher: Arc<Mutex<CommandDispatcher<'static>>>, http: Arc<Http>) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(TICK_INTERVAL);
+        let now = Local::now().naive_local();
+        dispatcher.lock().handle_tick(&http, now);
+    });
+}
@@ -66,4 +66,56 @@ impl ChatGameStorage {
             std::i32::MIN,
         )
     }
+
+    // (set, renamed to, merged into, keep the best result instead of adding up)
+    // All or nothing, so that retrying after a failure can't merge the same scores twice
+    pub fn archive_sets(&mut self, archives: &[(&str, String, String, bool)]) -> RedisResult<()> {
+        let mut con = redis!(self);
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        for (set, archive, total, keep_max) in archives {
+            let key = format!("{}-{}", self.key_prefix, set);
+            // RENAME fails when there's nothing to rename
+            if !con.exists(&key)? {
+                continue;
+            }
+            let total_key = format!("{}-{}", self.key_prefix, total);
+            let keys = [total_key.clone(), key.clone()];
+            if *keep_max {
+                pipe.zunionstore_max(total_key, &keys).ignore();
+            } else {
+                pipe.zunionstore(total_key, &keys).ignore();
+            }
+            pipe.rename(key, format!("{}-{}", self.key_prefix, archive))
+                .ignore();
+        }
+        pipe.query(&mut *con)
+    }
+
+    pub fn store_value(&mut self, key: &str, value: &str) -> RedisResult<()> {
+        redis!(self).set(format!("{}-{}", self.key_prefix, key), value)
+    }
+
+    pub fn fetch_value(&mut self, key: &str) -> RedisResult<Option<String>> {
+        redis!(self).get(format!("{}-{}", self.key_prefix, key))
+    }
+
+    pub fn remove_value(&mut self, key: &str) -> RedisResult<()> {
+        redis!(self).del(format!("{}-{}", self.key_prefix, key))
+    }
+
+    pub fn push_to_list(&mut self, list: &str, value: &str) -> RedisResult<()> {
+        redis!(self).rpush(format!("{}-{}", self.key_prefix, list), value)
+    }
+
+    pub fn fetch_list(&mut self, list: &str) -> RedisResult<Vec<String>> {
+        redis!(self).lrange(format!("{}-{}", self.key_prefix, list), 0, -1)
+    }
+
+    pub fn push_to_capped_list(&mut self, list: &str, value: &str, cap: isize) -> RedisResult<()> {
+        let key = format!("{}-{}", self.key_prefix, list);
+        let mut con = redis!(self);
+        let _: () = con.rpush(&key, value)?;
+        con.ltrim(key, -cap, -1)
+    }
 }